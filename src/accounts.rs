@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+use crate::api::AtheneClient;
+use crate::auth::{self, Credentials};
+use crate::config::Settings;
+use crate::convert::ConvertPolicy;
+use crate::error::AtheneError;
+use crate::events::Events;
+use crate::metrics::Metrics;
+use crate::scheduler::TaskSchedule;
+use crate::tasks::TaskRegistry;
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct User {
+    pub link: Option<String>,
+    pub access_token: Option<String>,
+    pub invite_code: Option<String>,
+    /// set when the last login failed, the link has to be renewed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub needs_login: bool,
+    /// overrides `convert.policy` of `config.json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub convert_policy: Option<ConvertPolicy>,
+    /// overrides `schedule` of `config.json`, task by task
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub schedule: HashMap<String, TaskSchedule>,
+    /// e.g. `{"premium_pick": false}`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tasks: HashMap<String, bool>,
+}
+
+impl Clone for User {
+    fn clone(&self) -> Self {
+        User {
+            link: self.link.clone(),
+            access_token: self.access_token.clone(),
+            invite_code: self.invite_code.clone(),
+            needs_login: self.needs_login,
+            convert_policy: self.convert_policy,
+            schedule: self.schedule.clone(),
+            tasks: self.tasks.clone(),
+        }
+    }
+}
+
+impl User {
+    pub fn invite_code(&self) -> &str {
+        self.invite_code.as_deref().unwrap_or("null")
+    }
+
+    pub fn convert_policy(&self, default: ConvertPolicy) -> ConvertPolicy {
+        self.convert_policy.unwrap_or(default)
+    }
+
+    /// Why the account can't login, `None` when it can or has a token.
+    pub fn login_problem(&self) -> Option<&'static str> {
+        match (&self.link, &self.access_token) {
+            (Some(link), _) if auth::parse_init_data(link).is_none() => {
+                Some("link has no tgWebAppData")
+            }
+            (None, None) => Some("needs a link or an access_token"),
+            _ if self.needs_login => Some("last login failed, renew the link"),
+            _ => None,
+        }
+    }
+}
+
+pub fn read_config_json(
+    file_path: &Path,
+) -> Result<HashMap<String, User>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(file_path)
+        .map_err(|e| format!("Unable to open {}: {}", file_path.display(), e))?;
+    let reader = std::io::BufReader::new(file);
+    let hashmap: HashMap<String, User> = serde_json::from_reader(reader)
+        .map_err(|e| format!("Unable to parse {}: {}", file_path.display(), e))?;
+    Ok(hashmap)
+}
+
+/// Replaces `file_path` through a temporary file, so a crash never leaves
+/// the links half written.
+pub fn write_config_json(file_path: &Path, data: &HashMap<String, User>) -> io::Result<()> {
+    let json_data = serde_json::to_string_pretty(data).expect("Unable to serialize data");
+    let tmp_path = file_path.with_extension("json.tmp");
+    fs::write(&tmp_path, json_data)?;
+    fs::rename(&tmp_path, file_path)
+}
+
+/// `user.json` shared by every job, so a renewed token is written back.
+/// Accounts given by `new` only live in memory.
+pub struct AccountStore {
+    file_path: Option<PathBuf>,
+    users: Mutex<HashMap<String, User>>,
+}
+
+impl AccountStore {
+    pub fn load(file_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut store = AccountStore::new(read_config_json(file_path)?)?;
+        store.file_path = Some(file_path.to_path_buf());
+        Ok(store)
+    }
+
+    pub fn new(users: HashMap<String, User>) -> Result<Self, Box<dyn std::error::Error>> {
+        for (name, user) in &users {
+            if let Some(policy) = &user.convert_policy {
+                policy.validate().map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+        Ok(AccountStore {
+            file_path: None,
+            users: Mutex::new(users),
+        })
+    }
+
+    /// Checks the `schedule` and `tasks` of every account against the tasks
+    /// of `registry`.
+    pub fn validate_tasks(&self, registry: &TaskRegistry) -> Result<(), String> {
+        for (name, user) in self.users.lock().unwrap().iter() {
+            registry
+                .validate_schedules(&user.schedule)
+                .and_then(|_| registry.validate_flags(&user.tasks))
+                .map_err(|e| format!("{}: {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn users(&self) -> HashMap<String, User> {
+        self.users.lock().unwrap().clone()
+    }
+
+    /// Sorted account names in `filter`, every account when it is empty.
+    pub fn select(&self, filter: &[String]) -> Result<Vec<String>, String> {
+        let users = self.users.lock().unwrap();
+        if let Some(unknown) = filter.iter().find(|name| !users.contains_key(*name)) {
+            return Err(format!("unknown account: {}", unknown));
+        }
+        let mut names: Vec<String> = users
+            .keys()
+            .filter(|name| filter.is_empty() || filter.contains(name))
+            .cloned()
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Stores `token` for `name` and persists the file.
+    pub fn set_token(&self, name: &str, token: &str) {
+        let mut users = self.users.lock().unwrap();
+        if let Some(user) = users.get_mut(name) {
+            user.access_token = Some(token.to_string());
+            user.needs_login = false;
+        }
+        self.write(&users);
+    }
+
+    /// Flags `name` as unable to login and persists the file.
+    pub fn mark_needs_login(&self, name: &str) {
+        let mut users = self.users.lock().unwrap();
+        if let Some(user) = users.get_mut(name) {
+            user.needs_login = true;
+        }
+        self.write(&users);
+    }
+
+    /// Failing to write only costs the token on the next start, the jobs
+    /// writing it keep running.
+    fn write(&self, users: &HashMap<String, User>) {
+        let Some(file_path) = &self.file_path else {
+            return;
+        };
+        if let Err(e) = write_config_json(file_path, users) {
+            error!("Unable to write {}: {}", file_path.display(), e);
+        }
+    }
+}
+
+/// Builds the client of `name` with the token stored in `user.json`.
+pub fn build_client(
+    name: &str,
+    user: &User,
+    settings: &Settings,
+    store: Arc<AccountStore>,
+    events: &Events,
+    metrics: &Arc<Metrics>,
+) -> AtheneClient {
+    let mut client = AtheneClient::new(name, settings)
+        .with_events(events.clone())
+        .with_metrics(metrics.clone());
+    if let Some(link) = &user.link {
+        client = client.with_credentials(Credentials {
+            link: link.clone(),
+            invite_code: user.invite_code().to_string(),
+        });
+    }
+    // a renewed token is written back to user.json
+    let name1 = name.to_string();
+    let client = client.on_token_refresh(move |token| store.set_token(&name1, token));
+    if let Some(access_token) = &user.access_token {
+        client.set_token(access_token);
+    }
+    client
+}
+
+/// Builds the client of `name`, logging in when `user.json` has no token yet.
+pub async fn init_client(
+    name: &str,
+    user: &User,
+    settings: &Settings,
+    store: Arc<AccountStore>,
+    events: &Events,
+    metrics: &Arc<Metrics>,
+) -> Result<Arc<AtheneClient>, AtheneError> {
+    let client = build_client(name, user, settings, store, events, metrics);
+    if client.token().is_none() {
+        client.login().await?;
+    }
+    Ok(Arc::new(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(file_path: PathBuf) -> AccountStore {
+        let users = serde_json::from_value(serde_json::json!({
+            "alice": { "link": null, "access_token": "old", "invite_code": null }
+        }))
+        .unwrap();
+        AccountStore {
+            file_path: Some(file_path),
+            ..AccountStore::new(users).unwrap()
+        }
+    }
+
+    #[test]
+    fn set_token_replaces_the_file() {
+        let dir = std::env::temp_dir().join(format!("athene-bot-{}-accounts", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("user.json");
+
+        store(file_path.clone()).set_token("alice", "new");
+        let users = read_config_json(&file_path).unwrap();
+        assert_eq!(users["alice"].access_token.as_deref(), Some("new"));
+        assert!(!file_path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn failed_write_keeps_the_token_in_memory() {
+        let file_path = std::env::temp_dir()
+            .join(format!("athene-bot-{}-missing", std::process::id()))
+            .join("user.json");
+        let store = store(file_path.clone());
+
+        store.set_token("alice", "new");
+        assert_eq!(store.users()["alice"].access_token.as_deref(), Some("new"));
+        assert!(!file_path.exists());
+    }
+}
@@ -0,0 +1,612 @@
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::auth::{self, Credentials};
+use crate::config::{RetrySettings, Settings};
+use crate::crypto;
+use crate::error::{AtheneError, ErrorKind};
+use crate::events::{BotEvent, Events};
+use crate::metrics::Metrics;
+use crate::models::{
+    check_message, decode_data, is_token_expired, LoginTelegramResponse, Mining, PremiumPick,
+    QuestList, TapEarn,
+};
+use crate::retry;
+use crate::tap::{self, Clock, SystemClock};
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
+use crate::utils;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TapData {
+    pub number_gem: f32,
+    pub number_ec: i32,
+    pub level: i32,
+    pub base_rate: f32,
+    pub min_ec: i32,
+    pub number_tap: i64,
+}
+
+impl TapData {
+    /// Gold collected at `now_ms`.
+    pub fn total_tap(&self, now_ms: i64) -> i64 {
+        tap::total_tap(self.number_tap, now_ms)
+    }
+
+    /// Timestamp in ms from which `total_tap` reaches `amount`.
+    pub fn reached_at(&self, amount: i64) -> i64 {
+        tap::reached_at(self.number_tap, amount)
+    }
+}
+
+/// Status and body of a successful (2xx) call.
+#[derive(Debug)]
+pub struct ApiResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+type TokenHook = Box<dyn Fn(&str) + Send + Sync>;
+
+/// One client per account: keeps the transport, the default headers
+/// and the access token, and exposes one method per miniapp endpoint.
+///
+/// When a call is rejected as unauthorized the client logs in again with its
+/// credentials, swaps the token for every job sharing it and retries once.
+pub struct AtheneClient {
+    name: String,
+    transport: Arc<dyn Transport>,
+    headers: HeaderMap,
+    api_base: String,
+    auth_base: String,
+    retry: RetrySettings,
+    convert_public_key: String,
+    pace: f64,
+    token: RwLock<Option<String>>,
+    credentials: Option<Credentials>,
+    /// serializes re-logins of jobs hitting an expired token together
+    relogin: Mutex<()>,
+    on_token_refresh: Option<TokenHook>,
+    clock: Arc<dyn Clock>,
+    events: Events,
+    metrics: Arc<Metrics>,
+}
+
+impl AtheneClient {
+    pub fn new(name: &str, settings: &Settings) -> Self {
+        let transport = ReqwestTransport::new(Duration::from_secs(settings.timeout_secs));
+        AtheneClient::with_transport(name, settings, Arc::new(transport))
+    }
+
+    pub fn with_transport(name: &str, settings: &Settings, transport: Arc<dyn Transport>) -> Self {
+        let mut headers = HeaderMap::new();
+        utils::init_headers(&mut headers, &settings.api_base);
+        AtheneClient {
+            name: name.to_string(),
+            transport,
+            headers,
+            api_base: settings.api_base.clone(),
+            auth_base: settings.auth_base.clone(),
+            retry: settings.retry.clone(),
+            convert_public_key: settings.convert_public_key.clone(),
+            pace: settings.pace,
+            token: RwLock::new(None),
+            credentials: None,
+            relogin: Mutex::new(()),
+            on_token_refresh: None,
+            clock: Arc::new(SystemClock),
+            events: Events::default(),
+            metrics: Arc::default(),
+        }
+    }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// `f` is called with every token obtained by `login`.
+    pub fn on_token_refresh(mut self, f: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_token_refresh = Some(Box::new(f));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Events of the client go to `events` instead of a log of its own.
+    pub fn with_events(mut self, events: Events) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Requests and tap data of the client are counted in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn emit(&self, event: BotEvent) {
+        self.events.emit(&self.name, event);
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Waits `secs` scaled by `pace`, the pause tasks make between calls.
+    pub async fn pause(&self, secs: u64) {
+        let delay = Duration::try_from_secs_f64(secs as f64 * self.pace).unwrap_or_default();
+        if !delay.is_zero() {
+            sleep(delay).await;
+        }
+    }
+
+    /// Encrypts a convert-gem payload with the configured public key.
+    pub fn encrypt(&self, payload: &str) -> Result<String, AtheneError> {
+        crypto::rsa_encrypt(payload, &self.convert_public_key)
+            .map_err(|e| AtheneError::new(ErrorKind::Encrypt, "post-convert-gem", e))
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.token.read().unwrap().clone()
+    }
+
+    pub fn set_token(&self, token: &str) {
+        *self.token.write().unwrap() = Some(token.to_string());
+    }
+
+    fn auth_headers(&self, token: Option<&str>) -> HeaderMap {
+        let mut headers = self.headers.clone();
+        if let Some(token) = token {
+            headers.insert(
+                COOKIE,
+                HeaderValue::from_str(&format!("token={}", token)).unwrap(),
+            );
+        }
+        headers
+    }
+
+    /// Sends `request` and turns transport failures and non-2xx statuses
+    /// into an `AtheneError` tagged with `endpoint`. Transient failures are
+    /// retried following the endpoint's retry policy.
+    async fn send(
+        &self,
+        endpoint: &'static str,
+        request: HttpRequest,
+    ) -> Result<ApiResponse, AtheneError> {
+        let policy = self.retry.policy(endpoint);
+        let mut attempt = 1;
+        loop {
+            let result = self.send_once(endpoint, request.clone()).await;
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            let Some(delay) = retry::retry_delay(endpoint, &err, &policy, attempt) else {
+                return Err(err);
+            };
+            self.emit(BotEvent::Warning(format!(
+                "{}, retry {}/{} in {:?}",
+                err,
+                attempt,
+                policy.max_attempts - 1,
+                delay
+            )));
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send_once(
+        &self,
+        endpoint: &'static str,
+        request: HttpRequest,
+    ) -> Result<ApiResponse, AtheneError> {
+        let started = Instant::now();
+        let response = self.transport.send(request).await;
+        let duration = started.elapsed();
+        let duration_ms = duration.as_millis() as u64;
+        let status = response.as_ref().ok().map(|r| r.status.as_u16());
+        self.metrics.request(&self.name, endpoint, status, duration);
+        let response = response.map_err(|e| {
+            warn!(endpoint, duration_ms, error = %e, "request failed");
+            AtheneError::transport(endpoint, e)
+        })?;
+        let status = response.status;
+        info!(endpoint, status = status.as_u16(), duration_ms, "request");
+        let retry_after = response
+            .headers
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(retry::parse_retry_after);
+        let body = response.body;
+        if !status.is_success() {
+            return Err(
+                AtheneError::from_status(endpoint, status, &body).with_retry_after(retry_after)
+            );
+        }
+        if is_token_expired(&body) {
+            return Err(
+                AtheneError::new(ErrorKind::Unauthorized, endpoint, "token expired")
+                    .with_status(status)
+                    .with_body(&body),
+            );
+        }
+        Ok(ApiResponse { status, body })
+    }
+
+    /// Sends an authenticated call, logging in again and retrying once when
+    /// the token is rejected.
+    async fn call(
+        &self,
+        endpoint: &'static str,
+        method: Method,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<ApiResponse, AtheneError> {
+        let token = self.token();
+        let result = self
+            .send(
+                endpoint,
+                self.request(&method, path, token.as_deref(), body.clone()),
+            )
+            .await;
+        match result {
+            Err(err) if err.kind == ErrorKind::Unauthorized && self.credentials.is_some() => {
+                self.emit(BotEvent::TokenExpired);
+                self.relogin(token.as_deref()).await?;
+                let token = self.token();
+                self.send(
+                    endpoint,
+                    self.request(&method, path, token.as_deref(), body),
+                )
+                .await
+            }
+            result => result,
+        }
+    }
+
+    fn request(
+        &self,
+        method: &Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<String>,
+    ) -> HttpRequest {
+        HttpRequest {
+            method: method.clone(),
+            url: format!("{}{}", self.api_base, path),
+            headers: self.auth_headers(token),
+            body,
+        }
+    }
+
+    /// Logs in again unless another job already replaced `stale` meanwhile.
+    async fn relogin(&self, stale: Option<&str>) -> Result<(), AtheneError> {
+        let _guard = self.relogin.lock().await;
+        if self.token().as_deref() != stale {
+            return Ok(());
+        }
+        self.login().await.map(|_| ())
+    }
+
+    async fn get(&self, endpoint: &'static str, path: &str) -> Result<ApiResponse, AtheneError> {
+        self.call(endpoint, Method::GET, path, None).await
+    }
+
+    /// Sends an action, a 2xx whose `message` is not `ok` is a refusal.
+    async fn post(
+        &self,
+        endpoint: &'static str,
+        path: &str,
+        body: String,
+    ) -> Result<ApiResponse, AtheneError> {
+        let response = self.call(endpoint, Method::POST, path, Some(body)).await?;
+        check_message(endpoint, &response.body)?;
+        Ok(response)
+    }
+
+    pub async fn get_tap_earn(&self) -> Result<TapData, AtheneError> {
+        let response = self
+            .get("get-tap-earn", "/api/get-tap-earn?lang=en")
+            .await?;
+        let tap: TapEarn = decode_data("get-tap-earn", &response.body)?;
+        let tap = tap.into();
+        self.metrics.tap(&self.name, &tap);
+        Ok(tap)
+    }
+
+    pub async fn post_convert_gem(&self, encrypt: String) -> Result<ApiResponse, AtheneError> {
+        let body = json!({
+            "encrypt": encrypt,
+        });
+        self.post(
+            "post-convert-gem",
+            "/api/post-convert-gem?lang=en",
+            body.to_string(),
+        )
+        .await
+    }
+
+    pub async fn get_mining_time(&self) -> Result<Option<i64>, AtheneError> {
+        let response = self.get("get-mining", "/api/get-mining").await?;
+        self.emit(BotEvent::Info(format!("get_mining: {:?}", response.status)));
+        let mining: Mining = decode_data("get-mining", &response.body)?;
+        Ok(mining.remain_time_next_claim)
+    }
+
+    pub async fn post_claim_gem(&self) -> Result<ApiResponse, AtheneError> {
+        self.post(
+            "post-claim-gem",
+            "/api/post-claim-gem?lang=en",
+            "{}".to_string(),
+        )
+        .await
+    }
+
+    pub async fn post_check_in(&self) -> Result<ApiResponse, AtheneError> {
+        self.post(
+            "post-check-in",
+            "/api/post-check-in?lang=en",
+            "{}".to_string(),
+        )
+        .await
+    }
+
+    pub async fn get_premium_pick(&self) -> Result<PremiumPick, AtheneError> {
+        let response = self
+            .get("get-premium-pick", "/api/get-premium-pick/?lang=en")
+            .await?;
+        decode_data("get-premium-pick", &response.body)
+    }
+
+    pub async fn post_premium_pick(&self, package_name: &str) -> Result<ApiResponse, AtheneError> {
+        let body = json!({
+            "packageName": package_name,
+        });
+        self.post(
+            "post-premium-pick",
+            "/api/post-premium-pick/?lang=en",
+            body.to_string(),
+        )
+        .await
+    }
+
+    pub async fn get_list_quest(&self) -> Result<QuestList, AtheneError> {
+        let response = self
+            .get(
+                "get-list-quest",
+                "/api/get-list-quest/?limit=50&offset=1&lang=en",
+            )
+            .await?;
+        decode_data("get-list-quest", &response.body)
+    }
+
+    pub async fn post_quest_reward(&self, quest: i64) -> Result<ApiResponse, AtheneError> {
+        let body = json!({
+            "quest": quest,
+        });
+        self.post(
+            "post-quest-reward",
+            "/api/post-quest-reward/?lang=en",
+            body.to_string(),
+        )
+        .await
+    }
+
+    /// Exchanges the telegram web app link for an access token and keeps it
+    /// on the client.
+    pub async fn login(&self) -> Result<String, AtheneError> {
+        let credentials = self.credentials.as_ref().ok_or_else(|| {
+            AtheneError::new(
+                ErrorKind::Unauthorized,
+                "login-telegram",
+                "no link to login with",
+            )
+        })?;
+        let token = auth::login_token(credentials).ok_or_else(|| {
+            AtheneError::new(
+                ErrorKind::Decode,
+                "login-telegram",
+                "link has no telegram web app data",
+            )
+        })?;
+        let body = json!({
+            "token": token,
+        });
+
+        let request = HttpRequest {
+            method: Method::POST,
+            url: format!("{}/api/v1.0/auth/login-telegram", self.auth_base),
+            headers: self.headers.clone(),
+            body: Some(body.to_string()),
+        };
+        let response = self.send("login-telegram", request).await?;
+        let val: LoginTelegramResponse = serde_json::from_str(&response.body)
+            .map_err(|e| AtheneError::decode("login-telegram", e.to_string(), &response.body))?;
+        let token = val.data.access_token;
+
+        let request = HttpRequest {
+            method: Method::POST,
+            url: format!("{}/api/login", self.api_base),
+            headers: self.headers.clone(),
+            body: Some(
+                json!({
+                    "token": token,
+                })
+                .to_string(),
+            ),
+        };
+        self.send("login", request).await?;
+
+        self.set_token(&token);
+        if let Some(f) = &self.on_token_refresh {
+            f(&token);
+        }
+        self.emit(BotEvent::LoginSucceeded);
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::fake::FakeTransport;
+    use crate::transport::HttpResponse;
+    use reqwest::header::COOKIE;
+    use std::sync::Mutex as StdMutex;
+
+    const TAP_EARN: &str = r#"{"message":"ok","data":{"numberGem":1.5,"numberEc":20,"level":2,"baseRate":0.1,"minEc":1000,"numberTap":1721900000000}}"#;
+
+    fn cookie(request: &HttpRequest) -> &str {
+        request.headers.get(COOKIE).unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn relogin_when_token_is_rejected() {
+        let transport = Arc::new(FakeTransport::new());
+        transport
+            .respond(Method::GET, "/api/get-tap-earn", 401, "")
+            .respond(Method::GET, "/api/get-tap-earn", 200, TAP_EARN)
+            .respond(
+                Method::POST,
+                "/api/v1.0/auth/login-telegram",
+                200,
+                r#"{"data":{"accessToken":"fresh"}}"#,
+            )
+            .respond(Method::POST, "/api/login", 200, r#"{"message":"ok"}"#);
+        let refreshed = Arc::new(StdMutex::new(Vec::new()));
+        let refreshed1 = refreshed.clone();
+        let client = AtheneClient::with_transport("test", &Settings::default(), transport.clone())
+            .with_credentials(Credentials {
+                link:
+                    "https://miniapp.athene.network/#tgWebAppData=query_id%3D1&tgWebAppVersion=7.6"
+                        .to_string(),
+                invite_code: "null".to_string(),
+            })
+            .on_token_refresh(move |token| refreshed1.lock().unwrap().push(token.to_string()));
+        client.set_token("stale");
+
+        let tap = client.get_tap_earn().await.unwrap();
+
+        assert_eq!(tap.min_ec, 1000);
+        let taps = transport.requests_to(Method::GET, "/api/get-tap-earn");
+        assert_eq!(cookie(&taps[0]), "token=stale");
+        assert_eq!(cookie(&taps[1]), "token=fresh");
+        assert_eq!(*refreshed.lock().unwrap(), vec!["fresh".to_string()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refusal_with_200_is_an_error() {
+        let transport = Arc::new(FakeTransport::new());
+        transport.respond(
+            Method::POST,
+            "/api/post-check-in",
+            200,
+            r#"{"message":"already checked in today"}"#,
+        );
+        let client = AtheneClient::with_transport("test", &Settings::default(), transport.clone());
+        client.set_token("token");
+
+        let err = client.post_check_in().await.unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::Rejected);
+        assert_eq!(err.endpoint, "post-check-in");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_expired_messages_are_not_a_dead_token() {
+        let transport = Arc::new(FakeTransport::new());
+        transport.respond(
+            Method::POST,
+            "/api/post-check-in",
+            200,
+            r#"{"message":"quest expired"}"#,
+        );
+        let client = AtheneClient::with_transport("test", &Settings::default(), transport.clone());
+        client.set_token("token");
+
+        let err = client.post_check_in().await.unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::Rejected);
+        assert!(transport
+            .requests_to(Method::POST, "/api/v1.0/auth/login-telegram")
+            .is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_waits_for_retry_after() {
+        let transport = Arc::new(FakeTransport::new());
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        transport
+            .respond_with(
+                Method::GET,
+                "/api/get-tap-earn",
+                HttpResponse {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    headers,
+                    body: String::new(),
+                },
+            )
+            .respond(Method::GET, "/api/get-tap-earn", 200, TAP_EARN);
+        let client = AtheneClient::with_transport("test", &Settings::default(), transport.clone());
+        client.set_token("token");
+
+        let started = tokio::time::Instant::now();
+        client.get_tap_earn().await.unwrap();
+
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+        assert_eq!(
+            transport
+                .requests_to(Method::GET, "/api/get-tap-earn")
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_reads_but_not_conversions() {
+        let transport = Arc::new(FakeTransport::new());
+        transport
+            .respond(Method::GET, "/api/get-tap-earn", 502, "")
+            .respond(Method::GET, "/api/get-tap-earn", 200, TAP_EARN)
+            .respond(Method::POST, "/api/post-convert-gem", 502, "");
+        let client = AtheneClient::with_transport("test", &Settings::default(), transport.clone());
+        client.set_token("token");
+
+        client.get_tap_earn().await.unwrap();
+        let err = client
+            .post_convert_gem("payload".to_string())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::Server);
+        assert_eq!(
+            transport
+                .requests_to(Method::GET, "/api/get-tap-earn")
+                .len(),
+            2
+        );
+        assert_eq!(
+            transport
+                .requests_to(Method::POST, "/api/post-convert-gem")
+                .len(),
+            1
+        );
+    }
+}
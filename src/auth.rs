@@ -0,0 +1,31 @@
+//! Telegram web app credentials the access token is obtained with.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::Url;
+
+/// Decoded `tgWebAppData` of a telegram web app link.
+pub fn parse_init_data(link: &str) -> Option<String> {
+    let url = Url::parse(link).ok()?;
+    let f = url.fragment()?;
+    let v = f.split('&').next()?;
+    let v = v.split('=').nth(1)?;
+    urlencoding::decode(v).ok().map(|s| s.to_string())
+}
+
+/// Telegram web app link and invite code used to (re)login.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub link: String,
+    pub invite_code: String,
+}
+
+/// `token` posted to `login-telegram`, `None` when the link has no
+/// telegram web app data.
+pub fn login_token(credentials: &Credentials) -> Option<String> {
+    let init_data = parse_init_data(&credentials.link)?;
+    Some(STANDARD.encode(format!(
+        "{}&invite_code={}",
+        init_data, credentials.invite_code
+    )))
+}
@@ -0,0 +1,492 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_cron_scheduler::JobScheduler;
+use tracing::{error, info, info_span, Instrument};
+
+use crate::accounts::{self, AccountStore};
+use crate::config::Settings;
+use crate::error::AtheneError;
+use crate::events::{AccountEvent, BotEvent, Events};
+use crate::metrics::{self, Metrics};
+use crate::scheduler::{self, Runner, TaskStatus};
+use crate::state::StateStore;
+use crate::tasks::{AccountContext, Task, TaskOutcome, TaskRegistry};
+
+/// Builds a [`Bot`], accounts and state default to the files of the
+/// settings.
+pub struct BotBuilder {
+    settings: Settings,
+    store: Option<Arc<AccountStore>>,
+    filter: Vec<String>,
+    registry: TaskRegistry,
+    state: Option<StateStore>,
+    log: bool,
+}
+
+impl BotBuilder {
+    /// Accounts to run instead of `settings.users_file`.
+    pub fn accounts(mut self, store: Arc<AccountStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Only runs these accounts, every account when empty.
+    pub fn only(mut self, names: Vec<String>) -> Self {
+        self.filter = names;
+        self
+    }
+
+    /// Tasks to run instead of `TaskRegistry::default`.
+    pub fn registry(mut self, registry: TaskRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Task state instead of `settings.state_file`, e.g.
+    /// `StateStore::default()` to keep it in memory.
+    pub fn state(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Turns off the log of what the bot does, `subscribe` still sees it.
+    pub fn quiet(mut self) -> Self {
+        self.log = false;
+        self
+    }
+
+    /// Fails on a `schedule` or `tasks` entry naming a task the registry
+    /// does not have.
+    pub fn build(self) -> Result<Bot, Box<dyn std::error::Error>> {
+        let store = match self.store {
+            Some(store) => store,
+            None => Arc::new(AccountStore::load(&self.settings.users_file)?),
+        };
+        self.registry.validate_schedules(&self.settings.schedule)?;
+        store.validate_tasks(&self.registry)?;
+        let names = store.select(&self.filter)?;
+        let state = match self.state {
+            Some(state) => state,
+            None => StateStore::load(&self.settings.state_file)?,
+        };
+        Ok(Bot {
+            settings: Arc::new(self.settings),
+            store,
+            names,
+            registry: self.registry,
+            runner: Runner {
+                events: Events::new(self.log),
+                state: Arc::new(state),
+                ..Runner::default()
+            },
+            sched: None,
+            metrics_server: None,
+            skipped: Vec::new(),
+        })
+    }
+}
+
+/// Handle of a running bot: schedules the tasks of every account on
+/// `start` until `stop`.
+pub struct Bot {
+    settings: Arc<Settings>,
+    store: Arc<AccountStore>,
+    names: Vec<String>,
+    registry: TaskRegistry,
+    runner: Runner,
+    sched: Option<JobScheduler>,
+    metrics_server: Option<JoinHandle<()>>,
+    skipped: Vec<(String, AtheneError)>,
+}
+
+impl Bot {
+    pub fn builder(settings: Settings) -> BotBuilder {
+        BotBuilder {
+            settings,
+            store: None,
+            filter: Vec::new(),
+            registry: TaskRegistry::default(),
+            state: None,
+            log: true,
+        }
+    }
+
+    /// Logs every account in and schedules its tasks. An account whose
+    /// login fails is skipped, fails only when every account was. Serves
+    /// the metrics when `metrics_addr` is set. A stopped bot can be started
+    /// again.
+    pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.sched.is_some() {
+            return Err("bot already started".into());
+        }
+        // the previous `stop` closed it for good
+        self.runner.in_flight = Arc::default();
+        if let Some(addr) = self.settings.metrics_addr {
+            let listener = TcpListener::bind(addr)
+                .await
+                .map_err(|e| format!("metrics_addr {}: {}", addr, e))?;
+            self.metrics_server = Some(metrics::serve(listener, self.runner.metrics.clone())?);
+        }
+        match self.schedule_accounts().await {
+            Ok(sched) => {
+                self.sched = Some(sched);
+                Ok(())
+            }
+            Err(err) => {
+                if let Some(server) = self.metrics_server.take() {
+                    server.abort();
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Logs in and schedules every account, returns the started scheduler.
+    async fn schedule_accounts(&mut self) -> Result<JobScheduler, Box<dyn std::error::Error>> {
+        let sched = JobScheduler::new().await?;
+        let users = self.store.users();
+        // one account failing to login must not keep the others from running
+        self.skipped.clear();
+        for name in &self.names {
+            let user = &users[name];
+            let client = accounts::init_client(
+                name,
+                user,
+                &self.settings,
+                self.store.clone(),
+                &self.runner.events,
+                &self.runner.metrics,
+            );
+            match client
+                .instrument(info_span!("account", account = %name))
+                .await
+            {
+                Ok(client) => {
+                    let ctx = AccountContext::new(client, user.clone(), self.settings.clone());
+                    scheduler::schedule_account(
+                        &sched,
+                        Arc::new(ctx),
+                        &self.registry,
+                        self.runner.clone(),
+                    )
+                    .await?
+                }
+                Err(err) => {
+                    self.runner
+                        .events
+                        .emit(name, BotEvent::Warning(format!("login_error: {}", err)));
+                    self.store.mark_needs_login(name);
+                    self.skipped.push((name.clone(), err));
+                }
+            }
+        }
+        if !self.skipped.is_empty() {
+            error!(
+                "{} account(s) skipped, renew their link in user.json:",
+                self.skipped.len()
+            );
+            for (name, err) in &self.skipped {
+                error!("  {}: {}", name, err);
+            }
+            if self.skipped.len() == self.names.len() {
+                return Err("no account could be started".into());
+            }
+        }
+        sched.start().await?;
+        Ok(sched)
+    }
+
+    /// Stops scheduling and lets running tasks finish (up to
+    /// `shutdown_timeout_secs`). Tokens are already in `user.json`, which
+    /// is left as it is so edits made while running survive.
+    pub async fn stop(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(mut sched) = self.sched.take() else {
+            return Ok(());
+        };
+        info!("shutting down, waiting for running tasks");
+        self.runner.in_flight.close();
+        sched.shutdown().await?;
+        let timeout = Duration::from_secs(self.settings.shutdown_timeout_secs);
+        if tokio::time::timeout(timeout, self.runner.in_flight.wait_idle())
+            .await
+            .is_err()
+        {
+            error!("tasks still running after {:?}, exiting anyway", timeout);
+        }
+        if let Some(server) = self.metrics_server.take() {
+            server.abort();
+        }
+        Ok(())
+    }
+
+    /// Events of every account from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountEvent> {
+        self.runner.events.subscribe()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.sched.is_some()
+    }
+
+    /// Selected account names.
+    pub fn accounts(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Accounts left out by the last `start`, with their login error.
+    pub fn skipped(&self) -> &[(String, AtheneError)] {
+        &self.skipped
+    }
+
+    /// Status of every task run so far, by account and task name.
+    pub fn status(&self) -> Vec<((String, &'static str), TaskStatus)> {
+        self.runner.stats.snapshot()
+    }
+
+    /// Metrics of every account, also served on `metrics_addr`.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.runner.metrics
+    }
+
+    pub fn registry(&self) -> &TaskRegistry {
+        &self.registry
+    }
+
+    /// Runs `task` once for the account of `ctx` outside the schedule,
+    /// recorded in the state like a scheduled run. `None` when its previous
+    /// run is still going.
+    pub async fn run_task(&self, ctx: &AccountContext, task: &dyn Task) -> Option<TaskOutcome> {
+        self.runner
+            .run_outcome(ctx.name(), task.name(), task.run(ctx))
+            .await
+    }
+
+    /// Context of `name` with a client that is not logged in yet, to run
+    /// tasks outside the schedule.
+    pub fn context(&self, name: &str) -> Option<AccountContext> {
+        let user = self.store.users().remove(name)?;
+        let client = accounts::build_client(
+            name,
+            &user,
+            &self.settings,
+            self.store.clone(),
+            &self.runner.events,
+            &self.runner.metrics,
+        );
+        Some(AccountContext::new(
+            Arc::new(client),
+            user,
+            self.settings.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::User;
+    use crate::mock::{MockServer, MockState, MOCK_LINK};
+    use crate::scheduler::TaskSchedule;
+    use std::collections::HashMap;
+
+    fn user(link: &str) -> User {
+        serde_json::from_value(serde_json::json!({
+            "link": link, "access_token": null, "invite_code": null
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn starts_and_stops_in_memory_accounts() {
+        let mock = MockServer::start(MockState::default()).await;
+        let users = HashMap::from([
+            ("alice".to_string(), user(MOCK_LINK)),
+            ("bob".to_string(), user("https://example.com/#nothing")),
+        ]);
+        let store = Arc::new(AccountStore::new(users).unwrap());
+        let mut bot = Bot::builder(mock.settings())
+            .accounts(store.clone())
+            .state(StateStore::default())
+            .quiet()
+            .build()
+            .unwrap();
+        let mut events = bot.subscribe();
+        assert_eq!(bot.accounts(), ["alice", "bob"]);
+
+        bot.start().await.unwrap();
+        assert!(bot.is_running());
+        assert!(bot.start().await.is_err());
+        assert_eq!(mock.state().check_ins, 1);
+        assert_eq!(bot.skipped().len(), 1);
+        assert_eq!(bot.skipped()[0].0, "bob");
+        assert!(store.users()["bob"].needs_login);
+        assert!(bot
+            .status()
+            .iter()
+            .any(|((name, task), s)| name == "alice" && *task == "check_in" && s.runs == 1));
+
+        let metrics = bot.metrics().render();
+        assert!(metrics.contains(
+            r#"athene_task_runs_total{account="alice",task="check_in",result="success"} 1"#
+        ));
+        assert!(metrics.contains(
+            r#"athene_requests_total{account="alice",endpoint="post-check-in",status="200"} 1"#
+        ));
+
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(event);
+        }
+        let alice = |event| AccountEvent {
+            account: "alice".to_string(),
+            event,
+        };
+        for event in [
+            BotEvent::LoginSucceeded,
+            BotEvent::CheckInDone,
+            BotEvent::GemClaimed,
+        ] {
+            assert!(seen.contains(&alice(event.clone())), "{:?}", event);
+        }
+        assert!(seen.iter().any(|e| e.account == "bob"
+            && matches!(&e.event, BotEvent::Warning(msg) if msg.starts_with("login_error"))));
+
+        bot.stop().await.unwrap();
+        assert!(!bot.is_running());
+        assert_eq!(
+            store.users()["alice"].access_token.as_deref(),
+            Some("mock-token-1")
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_keeps_edits_to_user_json() {
+        let mock = MockServer::start(MockState::default()).await;
+        let users_file = crate::mock::user_json("stop-keeps-edits");
+        let mut bot = Bot::builder(mock.settings())
+            .accounts(Arc::new(AccountStore::load(&users_file).unwrap()))
+            .state(StateStore::default())
+            .quiet()
+            .build()
+            .unwrap();
+        bot.start().await.unwrap();
+
+        let mut users: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&users_file).unwrap()).unwrap();
+        assert_eq!(users["alice"]["access_token"], "mock-token-1");
+        users["carol"] = serde_json::json!({ "link": MOCK_LINK });
+        std::fs::write(&users_file, users.to_string()).unwrap();
+        bot.stop().await.unwrap();
+
+        let users: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&users_file).unwrap()).unwrap();
+        assert_eq!(users["carol"]["link"], MOCK_LINK);
+    }
+
+    #[tokio::test]
+    async fn one_shot_runs_are_recorded() {
+        let mock = MockServer::start(MockState::default()).await;
+        let users = HashMap::from([("alice".to_string(), user(MOCK_LINK))]);
+        let bot = Bot::builder(mock.settings())
+            .accounts(Arc::new(AccountStore::new(users).unwrap()))
+            .state(StateStore::default())
+            .quiet()
+            .build()
+            .unwrap();
+        let ctx = bot.context("alice").unwrap();
+        ctx.client.login().await.unwrap();
+
+        let task = bot.registry().get("check_in").unwrap().clone();
+        let outcome = bot.run_task(&ctx, task.as_ref()).await.unwrap();
+
+        assert!(outcome.result.is_ok());
+        assert_eq!(mock.state().check_ins, 1);
+        let record = bot.runner.state.get("alice", "check_in").unwrap();
+        assert_eq!(record.last_success, Some(record.last_run));
+        assert_eq!(bot.status()[0].1.runs, 1);
+    }
+
+    struct Ping;
+
+    impl crate::tasks::Task for Ping {
+        fn name(&self) -> &'static str {
+            "ping"
+        }
+
+        fn default_schedule(&self) -> TaskSchedule {
+            TaskSchedule::Every(Duration::from_secs(60))
+        }
+
+        fn run<'a>(
+            &'a self,
+            _: &'a AccountContext,
+        ) -> futures::future::BoxFuture<'a, crate::tasks::TaskOutcome> {
+            Box::pin(async {
+                crate::tasks::TaskOutcome {
+                    result: Ok(()),
+                    next_run: None,
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn configures_tasks_of_the_registry() {
+        let build = |name: &str| {
+            let mut settings = Settings::default();
+            settings.schedule.insert(
+                name.to_string(),
+                TaskSchedule::try_from("every 1h".to_string()).unwrap(),
+            );
+            let mut alice = user(MOCK_LINK);
+            alice.tasks.insert(name.to_string(), false);
+            let users = HashMap::from([("alice".to_string(), alice)]);
+            let mut registry = TaskRegistry::default();
+            registry.register(Ping);
+            Bot::builder(settings)
+                .accounts(Arc::new(AccountStore::new(users).unwrap()))
+                .registry(registry)
+                .state(StateStore::default())
+                .build()
+        };
+        assert!(build("ping").is_ok());
+        let err = build("pong").err().unwrap().to_string();
+        assert!(err.contains("unknown task \"pong\""), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn restarts_after_stop() {
+        let mock = MockServer::start(MockState::default()).await;
+        let users = HashMap::from([("alice".to_string(), user(MOCK_LINK))]);
+        let mut settings = mock.settings();
+        settings.schedule.insert(
+            "check_in".to_string(),
+            TaskSchedule::try_from("every 1s".to_string()).unwrap(),
+        );
+        let mut bot = Bot::builder(settings)
+            .accounts(Arc::new(AccountStore::new(users).unwrap()))
+            .state(StateStore::default())
+            .quiet()
+            .build()
+            .unwrap();
+
+        bot.start().await.unwrap();
+        assert_eq!(mock.state().check_ins, 1);
+        bot.stop().await.unwrap();
+
+        bot.start().await.unwrap();
+        assert!(bot.is_running());
+        // resumed a second after the first run
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while mock.state().check_ins < 2 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("check-in never ran again");
+        bot.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "athene_bot",
+    version,
+    about = "A self-hosted athene network bot"
+)]
+pub struct Cli {
+    /// config file, `user.json` is looked up next to it; without it
+    /// `config.json` is read when it exists
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// only act on this account, can be repeated
+    #[arg(long = "account", value_name = "NAME", global = true)]
+    pub accounts: Vec<String>,
+
+    /// `human` for colored lines, `json` for one json object per line
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Human)]
+    pub log_format: LogFormat,
+
+    /// log tokens, cookies and telegram init data in clear, local debugging
+    /// only
+    #[arg(long, global = true)]
+    pub unsafe_log_secrets: bool,
+
+    /// defaults to `run`
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LogFormat {
+    Human,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// schedule every task and keep running
+    Run,
+    /// run every task once and exit
+    Once,
+    /// login again and store the new access token
+    Login,
+    /// print gem, gold and mining state
+    Status,
+    /// daily check-in
+    CheckIn,
+    /// claim the mined gem
+    Claim,
+    /// convert gold to gem
+    Convert,
+    /// claim the finished quests
+    Quests,
+    /// inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// load config and accounts, report what is wrong
+    Validate,
+}
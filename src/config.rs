@@ -0,0 +1,318 @@
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::convert::ConvertPolicy;
+use crate::crypto;
+use crate::scheduler::TaskSchedule;
+
+pub const DEFAULT_API_BASE: &str = "https://miniapp.athene.network";
+pub const DEFAULT_AUTH_BASE: &str = "https://athene.network";
+
+/// Bot wide settings, read from `config.json` and overridable by env vars.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// miniapp api host, e.g. `https://miniapp.athene.network`
+    pub api_base: String,
+    /// host serving `/api/v1.0/auth/login-telegram`
+    pub auth_base: String,
+    /// per request timeout
+    pub timeout_secs: u64,
+    pub retry: RetrySettings,
+    /// PEM public key the convert-gem payload is encrypted with
+    pub convert_public_key: String,
+    /// accounts file, relative paths are resolved next to the config file
+    pub users_file: PathBuf,
+    /// last run of every task, resumed on restart, resolved like `users_file`
+    pub state_file: PathBuf,
+    /// how long running tasks may take to finish on shutdown
+    pub shutdown_timeout_secs: u64,
+    /// scales the pauses tasks make before and between calls, `0` skips them
+    pub pace: f64,
+    /// serves prometheus metrics on `/metrics`, e.g. `"127.0.0.1:9100"`,
+    /// off when unset
+    pub metrics_addr: Option<SocketAddr>,
+    pub claim: ClaimSettings,
+    pub convert: ConvertSettings,
+    /// schedule by task name, e.g. `"check_in": "every 12h"`
+    pub schedule: HashMap<String, TaskSchedule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            api_base: DEFAULT_API_BASE.to_string(),
+            auth_base: DEFAULT_AUTH_BASE.to_string(),
+            timeout_secs: 30,
+            retry: RetrySettings::default(),
+            convert_public_key: crypto::CONVERT_PUBLIC_KEY.to_string(),
+            users_file: PathBuf::from("user.json"),
+            state_file: PathBuf::from("state.json"),
+            shutdown_timeout_secs: 30,
+            pace: 1.0,
+            metrics_addr: None,
+            claim: ClaimSettings::default(),
+            convert: ConvertSettings::default(),
+            schedule: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// total attempts, 1 disables retrying
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// `base * 2^(attempt - 1)`, capped at `max_delay_ms`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.base_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay_ms)
+    }
+}
+
+/// Default retry policy plus overrides keyed by endpoint name,
+/// e.g. `"post-claim-gem"`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RetrySettings {
+    #[serde(flatten)]
+    pub default: RetryPolicy,
+    pub endpoints: HashMap<String, RetryPolicy>,
+}
+
+impl RetrySettings {
+    pub fn policy(&self, endpoint: &str) -> RetryPolicy {
+        self.endpoints
+            .get(endpoint)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// When to claim the mined gem again.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ClaimSettings {
+    /// added to `remainTimeNextClaim` so the claim lands after mining ended
+    pub margin_secs: u64,
+    /// used when the remaining time is unknown
+    pub fallback_poll_secs: u64,
+    /// longest wait between two claims, whatever the server reports
+    pub max_wait_secs: u64,
+    /// wait after a failed claim
+    pub retry_after_error_secs: u64,
+}
+
+impl Default for ClaimSettings {
+    fn default() -> Self {
+        ClaimSettings {
+            margin_secs: 30,
+            fallback_poll_secs: 60 * 30,
+            max_wait_secs: 60 * 60 * 12,
+            retry_after_error_secs: 120,
+        }
+    }
+}
+
+impl ClaimSettings {
+    /// Delay until the next claim, `remaining` being the seconds reported by
+    /// `get-mining`, at most `max_wait_secs`.
+    pub fn next_run(&self, remaining: Option<i64>) -> Duration {
+        match remaining {
+            Some(secs) => Duration::from_secs(
+                (secs.max(0) as u64)
+                    .saturating_add(self.margin_secs)
+                    .min(self.max_wait_secs),
+            ),
+            None => Duration::from_secs(self.fallback_poll_secs),
+        }
+    }
+}
+
+/// When to convert gold to gem again.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ConvertSettings {
+    /// default of the accounts without their own `convert_policy`
+    pub policy: ConvertPolicy,
+    /// added to the predicted time the gold reaches `minEc`
+    pub margin_secs: u64,
+    /// longest wait between two looks at the gold, whatever the prediction
+    pub safety_poll_secs: u64,
+    /// wait after a failed look or conversion
+    pub retry_after_error_secs: u64,
+}
+
+impl Default for ConvertSettings {
+    fn default() -> Self {
+        ConvertSettings {
+            policy: ConvertPolicy::Minimum,
+            margin_secs: 5,
+            safety_poll_secs: 60 * 60,
+            retry_after_error_secs: 120,
+        }
+    }
+}
+
+impl ConvertSettings {
+    /// Delay until the next conversion, `wait_ms` being the predicted time
+    /// left until enough gold is collected, `None` when unknown or never.
+    pub fn next_run(&self, wait_ms: Option<i64>) -> Duration {
+        let safety_poll = Duration::from_secs(self.safety_poll_secs);
+        match wait_ms {
+            Some(ms) => (Duration::from_millis(ms.max(0) as u64)
+                + Duration::from_secs(self.margin_secs))
+            .min(safety_poll),
+            None => safety_poll,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads `file_path` when it exists, then applies `ATHENE_API_BASE` and
+    /// `ATHENE_AUTH_BASE` on top.
+    pub fn load(file_path: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
+        let mut settings: Settings = if file_path.exists() {
+            let content = fs::read_to_string(file_path)?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Unable to parse {}: {}", file_path.display(), e))?
+        } else {
+            Settings::default()
+        };
+
+        if let Ok(v) = std::env::var("ATHENE_API_BASE") {
+            settings.api_base = v;
+        }
+        if let Ok(v) = std::env::var("ATHENE_AUTH_BASE") {
+            settings.auth_base = v;
+        }
+        settings.api_base = settings.api_base.trim_end_matches('/').to_string();
+        settings.auth_base = settings.auth_base.trim_end_matches('/').to_string();
+        let dir = file_path.parent().unwrap_or(Path::new(""));
+        if settings.users_file.is_relative() {
+            settings.users_file = dir.join(&settings.users_file);
+        }
+        if settings.state_file.is_relative() {
+            settings.state_file = dir.join(&settings.state_file);
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Like `load`, but fails when `file_path` does not exist, for a file
+    /// the user asked for.
+    pub fn load_required(file_path: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
+        if !file_path.exists() {
+            return Err(format!("{} does not exist", file_path.display()).into());
+        }
+        Settings::load(file_path)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (key, base) in [("api_base", &self.api_base), ("auth_base", &self.auth_base)] {
+            Url::parse(base).map_err(|e| format!("{}: invalid url {:?}: {}", key, base, e))?;
+        }
+        if self.retry.default.max_attempts == 0
+            || self.retry.endpoints.values().any(|p| p.max_attempts == 0)
+        {
+            return Err("retry: max_attempts must be at least 1".to_string());
+        }
+        if !(self.pace.is_finite() && self.pace >= 0.0) {
+            return Err(format!(
+                "pace: {} must be a number of at least 0",
+                self.pace
+            ));
+        }
+        let claim = &self.claim;
+        if [
+            claim.fallback_poll_secs,
+            claim.max_wait_secs,
+            claim.retry_after_error_secs,
+        ]
+        .contains(&0)
+        {
+            return Err(
+                "claim: fallback_poll_secs, max_wait_secs and retry_after_error_secs must be at least 1"
+                    .to_string(),
+            );
+        }
+        if self.convert.safety_poll_secs == 0 || self.convert.retry_after_error_secs == 0 {
+            return Err(
+                "convert: safety_poll_secs and retry_after_error_secs must be at least 1"
+                    .to_string(),
+            );
+        }
+        self.convert.policy.validate()?;
+        crypto::parse_public_key(&self.convert_public_key)
+            .map_err(|e| format!("convert_public_key: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_bad_convert_public_key() {
+        let settings = Settings {
+            convert_public_key: "-----BEGIN PUBLIC KEY-----\nnope\n-----END PUBLIC KEY-----"
+                .to_string(),
+            ..Settings::default()
+        };
+        let err = settings.validate().unwrap_err();
+        assert!(err.starts_with("convert_public_key:"), "{}", err);
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_a_negative_pace() {
+        for pace in [-1.0, f64::NAN] {
+            let settings = Settings {
+                pace,
+                ..Settings::default()
+            };
+            let err = settings.validate().unwrap_err();
+            assert!(err.starts_with("pace:"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn only_a_required_config_must_exist() {
+        let missing = std::env::temp_dir().join(format!(
+            "athene-bot-{}-missing-config.json",
+            std::process::id()
+        ));
+        assert!(Settings::load(&missing).is_ok());
+        let err = Settings::load_required(&missing).unwrap_err();
+        assert!(err.to_string().ends_with("does not exist"), "{}", err);
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::TapData;
+
+/// When gold is converted to gem, set in `config.json` and overridable per
+/// account in `user.json`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConvertPolicy {
+    /// as soon as the server allows it, `"minimum"`
+    #[default]
+    Minimum,
+    /// once the gold reaches n times the server minimum, `{"multiple": n}`
+    Multiple(i64),
+    /// once the gold reaches an amount, `{"target": 10000}`
+    Target(i64),
+    /// keep accumulating, `"never"`
+    Never,
+}
+
+impl ConvertPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ConvertPolicy::Multiple(n) if *n < 1 => Err(format!(
+                "convert policy: multiple must be at least 1, got {}",
+                n
+            )),
+            ConvertPolicy::Target(n) if *n < 1 => Err(format!(
+                "convert policy: target must be at least 1, got {}",
+                n
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// convert this much gold now
+    Convert(i64),
+    /// not enough gold before this timestamp in ms
+    Wait(i64),
+    /// the policy never converts
+    Hold,
+}
+
+/// What to do with the gold of `tap_data` at `now_ms`.
+pub fn decide(policy: ConvertPolicy, tap_data: &TapData, now_ms: i64) -> Decision {
+    let min_ec = tap_data.min_ec as i64;
+    let threshold = match policy {
+        ConvertPolicy::Minimum => min_ec,
+        ConvertPolicy::Multiple(n) => min_ec.saturating_mul(n),
+        // the server refuses anything below its minimum
+        ConvertPolicy::Target(n) => n.max(min_ec),
+        ConvertPolicy::Never => return Decision::Hold,
+    };
+    let total_tap = tap_data.total_tap(now_ms);
+    if total_tap >= threshold {
+        Decision::Convert(total_tap)
+    } else {
+        Decision::Wait(tap_data.reached_at(threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// counter started at 0, 1000 gold minimum
+    fn tap_data() -> TapData {
+        TapData {
+            number_gem: 0.0,
+            number_ec: 0,
+            level: 1,
+            base_rate: 0.001,
+            min_ec: 1000,
+            number_tap: 0,
+        }
+    }
+
+    /// time at which `gold` has been collected
+    fn at(gold: i64) -> i64 {
+        (gold + 100) * 100
+    }
+
+    #[test]
+    fn minimum_converts_everything_once_allowed() {
+        let tap_data = tap_data();
+        assert_eq!(
+            decide(ConvertPolicy::Minimum, &tap_data, at(999)),
+            Decision::Wait(at(1000))
+        );
+        assert_eq!(
+            decide(ConvertPolicy::Minimum, &tap_data, at(1000)),
+            Decision::Convert(1000)
+        );
+        assert_eq!(
+            decide(ConvertPolicy::Minimum, &tap_data, at(4321) + 99),
+            Decision::Convert(4321)
+        );
+    }
+
+    #[test]
+    fn multiple_waits_for_n_times_the_minimum() {
+        let tap_data = tap_data();
+        let policy = ConvertPolicy::Multiple(3);
+        assert_eq!(
+            decide(policy, &tap_data, at(2999)),
+            Decision::Wait(at(3000))
+        );
+        assert_eq!(decide(policy, &tap_data, at(3500)), Decision::Convert(3500));
+    }
+
+    #[test]
+    fn target_is_never_below_the_minimum() {
+        let tap_data = tap_data();
+        assert_eq!(
+            decide(ConvertPolicy::Target(10_000), &tap_data, at(9_999)),
+            Decision::Wait(at(10_000))
+        );
+        assert_eq!(
+            decide(ConvertPolicy::Target(10_000), &tap_data, at(10_000)),
+            Decision::Convert(10_000)
+        );
+        assert_eq!(
+            decide(ConvertPolicy::Target(10), &tap_data, at(500)),
+            Decision::Wait(at(1000))
+        );
+    }
+
+    #[test]
+    fn never_holds_whatever_the_gold() {
+        assert_eq!(
+            decide(ConvertPolicy::Never, &tap_data(), at(1_000_000)),
+            Decision::Hold
+        );
+    }
+
+    #[test]
+    fn policy_from_json() {
+        let parse = |s| serde_json::from_str::<ConvertPolicy>(s).unwrap();
+        assert_eq!(parse(r#""minimum""#), ConvertPolicy::Minimum);
+        assert_eq!(parse(r#"{"multiple": 10}"#), ConvertPolicy::Multiple(10));
+        assert_eq!(parse(r#"{"target": 10000}"#), ConvertPolicy::Target(10_000));
+        assert_eq!(parse(r#""never""#), ConvertPolicy::Never);
+        assert!(ConvertPolicy::Multiple(0).validate().is_err());
+        assert!(ConvertPolicy::Target(-1).validate().is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! Encryption of the convert-gem payload.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rsa::Oaep;
+use rsa::{pkcs8::DecodePublicKey, RsaPublicKey};
+use sha2::Sha256;
+
+/// key the miniapp encrypts the convert-gem payload with
+pub const CONVERT_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAwmkourU0WsNzc0mcb6a7
+xRBgN4FaA7ak/82zeIMBQf0/uTY42p3uW5IgSx56DpnuGnrFAYLDDDu96lhRNpNK
+r6uaIBuhX7tg+4m26KDAEDHyTWgXEsZkClTeAl15gP0NoKXYGCr+rGBe2uvjFVJU
+ML+J9kPIVnJ99jxJ8EWiRJ9L/qhr5C04Z1QhNnF3fiaaeXlGgQLwwbSIKnc3ypuw
+E/0zrAGJ+1WiidCbQqqclGglpcSFWtF8znte+H/jlk1+0rypil4AkNPdN+mbAb8w
+HkQhWA50hXFxmCnIJjw10YfZAFkMzaFKNVZpOmnTvZrmlQShsOLUci8q45kN1jsU
+pQIDAQAB
+-----END PUBLIC KEY-----";
+
+pub fn parse_public_key(key: &str) -> Result<RsaPublicKey, String> {
+    RsaPublicKey::from_public_key_pem(key).map_err(|e| format!("invalid PEM public key: {}", e))
+}
+
+/// RSA-OAEP (SHA-256) encryption with a PEM public key, base64 encoded.
+pub fn rsa_encrypt(t: &str, key: &str) -> Result<String, String> {
+    let public_key = parse_public_key(key)?;
+
+    let mut rng = rand::thread_rng();
+    let padding = Oaep::new::<Sha256>();
+    let encrypted_data = public_key
+        .encrypt(&mut rng, padding, t.as_bytes())
+        .map_err(|e| e.to_string())?;
+    Ok(STANDARD.encode(&encrypted_data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_key_is_an_error() {
+        assert!(parse_public_key(CONVERT_PUBLIC_KEY).is_ok());
+        assert!(rsa_encrypt("payload", CONVERT_PUBLIC_KEY).is_ok());
+        assert!(parse_public_key("not a key").is_err());
+        assert!(rsa_encrypt("payload", "not a key").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use reqwest::StatusCode;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::redact;
+use crate::transport::TransportError;
+
+/// how many chars of a response body are kept on an error
+const BODY_EXCERPT_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 401/403, the access token has to be renewed
+    Unauthorized,
+    /// 429
+    RateLimited,
+    /// 5xx
+    Server,
+    /// the payload did not match the expected model
+    Decode,
+    /// connect error, timeout or broken body
+    Transport,
+    /// any other refusal: 4xx or a `message` other than `ok`
+    Rejected,
+    /// the payload could not be encrypted, nothing was sent
+    Encrypt,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ErrorKind::Unauthorized => "unauthorized",
+            ErrorKind::RateLimited => "rate limited",
+            ErrorKind::Server => "server error",
+            ErrorKind::Decode => "decode error",
+            ErrorKind::Transport => "transport error",
+            ErrorKind::Rejected => "rejected",
+            ErrorKind::Encrypt => "encrypt error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug)]
+pub struct AtheneError {
+    pub kind: ErrorKind,
+    pub endpoint: &'static str,
+    pub status: Option<StatusCode>,
+    /// first chars of the response body, if any was read
+    pub body: Option<String>,
+    pub detail: String,
+    /// `Retry-After` sent along a 429
+    pub retry_after: Option<Duration>,
+    /// false when the request never reached the server, e.g. connect refused
+    pub request_sent: bool,
+}
+
+impl AtheneError {
+    pub fn new(kind: ErrorKind, endpoint: &'static str, detail: impl Into<String>) -> Self {
+        AtheneError {
+            kind,
+            endpoint,
+            status: None,
+            body: None,
+            detail: detail.into(),
+            retry_after: None,
+            request_sent: true,
+        }
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_body(mut self, body: &str) -> Self {
+        self.body = Some(excerpt(body));
+        self
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub fn transport(endpoint: &'static str, err: TransportError) -> Self {
+        let mut e = AtheneError::new(ErrorKind::Transport, endpoint, err.message);
+        e.request_sent = !err.connect;
+        e
+    }
+
+    /// Maps a non-2xx status to its kind.
+    pub fn from_status(endpoint: &'static str, status: StatusCode, body: &str) -> Self {
+        let kind = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => ErrorKind::RateLimited,
+            s if s.is_server_error() => ErrorKind::Server,
+            _ => ErrorKind::Rejected,
+        };
+        AtheneError::new(kind, endpoint, format!("unexpected status {}", status))
+            .with_status(status)
+            .with_body(body)
+    }
+
+    pub fn decode(endpoint: &'static str, detail: impl Into<String>, body: &str) -> Self {
+        AtheneError::new(ErrorKind::Decode, endpoint, detail).with_body(body)
+    }
+}
+
+impl Display for AtheneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut s = format!("{} {}", self.endpoint, self.kind);
+        if let Some(status) = self.status {
+            s += &format!(" ({})", status);
+        }
+        s += &format!(": {}", self.detail);
+        if let Some(body) = &self.body {
+            s += &format!(", body: {:?}", body);
+        }
+        f.write_str(&redact::redact(&s))
+    }
+}
+
+impl std::error::Error for AtheneError {}
+
+/// Start of `body`, secrets masked.
+fn excerpt(body: &str) -> String {
+    let body = redact::redact(body);
+    match body.char_indices().nth(BODY_EXCERPT_LEN) {
+        Some((i, _)) => format!("{}...", &body[..i]),
+        None => body.to_string(),
+    }
+}
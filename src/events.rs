@@ -0,0 +1,126 @@
+//! What the bot does, broadcast to whoever embeds it.
+
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::redact;
+
+/// events a slow subscriber may lag behind before losing the oldest
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotEvent {
+    LoginSucceeded,
+    /// the token was rejected, a login follows
+    TokenExpired,
+    CheckInDone,
+    GemClaimed,
+    /// gold converted to gem
+    GemsConverted {
+        amount: i64,
+    },
+    QuestClaimed {
+        id: i64,
+    },
+    PremiumPackageClaimed {
+        name: String,
+    },
+    TaskFailed {
+        task: &'static str,
+        error: String,
+        runs: u64,
+        errors: u64,
+    },
+    /// progress without an event of its own
+    Info(String),
+    /// trouble without an event of its own
+    Warning(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountEvent {
+    pub account: String,
+    pub event: BotEvent,
+}
+
+/// Sends the events of a bot to every subscriber. The log is the one
+/// subscriber called in line, so its lines keep their order even when the
+/// process exits right after.
+#[derive(Clone)]
+pub struct Events {
+    tx: broadcast::Sender<AccountEvent>,
+    log: bool,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Events::new(true)
+    }
+}
+
+impl Events {
+    /// `log` turns the log subscriber on.
+    pub fn new(log: bool) -> Self {
+        Events {
+            tx: broadcast::channel(CAPACITY).0,
+            log,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Sends `event`, secrets masked in its text.
+    pub fn emit(&self, account: &str, event: BotEvent) {
+        let event = match event {
+            BotEvent::Info(msg) => BotEvent::Info(redact::redact(&msg).into_owned()),
+            BotEvent::Warning(msg) => BotEvent::Warning(redact::redact(&msg).into_owned()),
+            BotEvent::TaskFailed {
+                task,
+                error,
+                runs,
+                errors,
+            } => BotEvent::TaskFailed {
+                task,
+                error: redact::redact(&error).into_owned(),
+                runs,
+                errors,
+            },
+            event => event,
+        };
+        let event = AccountEvent {
+            account: account.to_string(),
+            event,
+        };
+        if self.log {
+            log_event(&event);
+        }
+        // no subscriber is fine
+        let _ = self.tx.send(event);
+    }
+}
+
+/// The log subscriber, with the account and the data of the event as
+/// fields.
+pub fn log_event(AccountEvent { account, event }: &AccountEvent) {
+    match event {
+        BotEvent::LoginSucceeded => info!(account, "login: ok"),
+        BotEvent::TokenExpired => warn!(account, "token expired, login again"),
+        BotEvent::CheckInDone => info!(account, "check-in: done"),
+        BotEvent::GemClaimed => info!(account, "claim-gem: done"),
+        BotEvent::GemsConverted { amount } => info!(account, amount, "convert-gem: done"),
+        BotEvent::QuestClaimed { id } => info!(account, quest = id, "quest-reward: claimed"),
+        BotEvent::PremiumPackageClaimed { name } => {
+            info!(account, package = name, "premium-pick: claimed")
+        }
+        BotEvent::TaskFailed {
+            task,
+            error,
+            runs,
+            errors,
+        } => error!(account, task, error, runs, errors, "{}_error", task),
+        BotEvent::Info(msg) => info!(account, "{}", msg),
+        BotEvent::Warning(msg) => warn!(account, "{}", msg),
+    }
+}
@@ -0,0 +1,41 @@
+//! Self-hosted athene network bot, embeddable in another service:
+//!
+//! ```no_run
+//! use athene_bot::bot::Bot;
+//! use athene_bot::config::Settings;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let settings = Settings::load("config.json".as_ref())?;
+//! let mut bot = Bot::builder(settings).build()?;
+//! bot.start().await?;
+//! // ...
+//! println!("{:?}", bot.status());
+//! bot.stop().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod accounts;
+pub mod api;
+pub mod auth;
+pub mod bot;
+pub mod config;
+pub mod convert;
+pub mod crypto;
+pub mod error;
+pub mod events;
+pub mod metrics;
+#[cfg(test)]
+mod mock;
+pub mod models;
+pub mod redact;
+mod retry;
+pub mod scheduler;
+pub mod state;
+pub mod tap;
+pub mod tasks;
+pub mod transport;
+pub mod utils;
+
+pub use bot::{Bot, BotBuilder};
+pub use events::{AccountEvent, BotEvent};
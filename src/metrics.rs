@@ -0,0 +1,317 @@
+//! Prometheus metrics of every account, served on `/metrics` when
+//! `metrics_addr` is set.
+
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use axum::Router;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::api::TapData;
+
+/// upper bounds of the request latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// one count per bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|le| secs <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// by account, endpoint and status, `error` when nothing came back
+    requests: BTreeMap<(String, &'static str, String), u64>,
+    latency: BTreeMap<(String, &'static str), Histogram>,
+    /// by account, task and `success` or `failure`
+    task_runs: BTreeMap<(String, &'static str, &'static str), u64>,
+    /// seconds since the epoch
+    last_success: BTreeMap<(String, &'static str), f64>,
+    /// `number_gem`, `number_ec` and `level` of the latest `TapData`
+    tap: BTreeMap<String, (f64, i64, i64)>,
+}
+
+/// Counters, histograms and gauges of a bot, rendered in the Prometheus
+/// text format.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+impl Metrics {
+    /// One request of `account` to `endpoint`, `status` being `None` when
+    /// it failed before a response.
+    pub fn request(
+        &self,
+        account: &str,
+        endpoint: &'static str,
+        status: Option<u16>,
+        duration: Duration,
+    ) {
+        let status = status.map_or("error".to_string(), |s| s.to_string());
+        let mut inner = self.inner.lock().unwrap();
+        *inner
+            .requests
+            .entry((account.to_string(), endpoint, status))
+            .or_default() += 1;
+        inner
+            .latency
+            .entry((account.to_string(), endpoint))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// One run of `task`, ending at `now_ms`.
+    pub fn task_run(&self, account: &str, task: &'static str, ok: bool, now_ms: i64) {
+        let mut inner = self.inner.lock().unwrap();
+        let result = if ok { "success" } else { "failure" };
+        *inner
+            .task_runs
+            .entry((account.to_string(), task, result))
+            .or_default() += 1;
+        if ok {
+            inner
+                .last_success
+                .insert((account.to_string(), task), now_ms as f64 / 1000.0);
+        }
+    }
+
+    /// Last success of `task` before a restart, kept until it runs again.
+    pub fn task_succeeded_at(&self, account: &str, task: &'static str, at_ms: i64) {
+        self.inner
+            .lock()
+            .unwrap()
+            .last_success
+            .entry((account.to_string(), task))
+            .or_insert(at_ms as f64 / 1000.0);
+    }
+
+    pub fn tap(&self, account: &str, tap: &TapData) {
+        self.inner.lock().unwrap().tap.insert(
+            account.to_string(),
+            (
+                tap.number_gem as f64,
+                tap.number_ec as i64,
+                tap.level as i64,
+            ),
+        );
+    }
+
+    /// Everything in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "athene_requests_total", "counter", "api requests");
+        for ((account, endpoint, status), n) in &inner.requests {
+            let _ = writeln!(
+                out,
+                "athene_requests_total{{account=\"{}\",endpoint=\"{}\",status=\"{}\"}} {}",
+                escape(account),
+                endpoint,
+                status,
+                n
+            );
+        }
+
+        header(
+            &mut out,
+            "athene_request_duration_seconds",
+            "histogram",
+            "api request latency",
+        );
+        for ((account, endpoint), h) in &inner.latency {
+            let labels = format!("account=\"{}\",endpoint=\"{}\"", escape(account), endpoint);
+            let mut cumulative = 0;
+            for (le, n) in LATENCY_BUCKETS.iter().zip(h.buckets) {
+                cumulative += n;
+                let _ = writeln!(
+                    out,
+                    "athene_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "athene_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, h.count
+            );
+            let _ = writeln!(
+                out,
+                "athene_request_duration_seconds_sum{{{}}} {}",
+                labels, h.sum
+            );
+            let _ = writeln!(
+                out,
+                "athene_request_duration_seconds_count{{{}}} {}",
+                labels, h.count
+            );
+        }
+
+        header(&mut out, "athene_task_runs_total", "counter", "task runs");
+        for ((account, task, result), n) in &inner.task_runs {
+            let _ = writeln!(
+                out,
+                "athene_task_runs_total{{account=\"{}\",task=\"{}\",result=\"{}\"}} {}",
+                escape(account),
+                task,
+                result,
+                n
+            );
+        }
+
+        header(
+            &mut out,
+            "athene_task_last_success_timestamp_seconds",
+            "gauge",
+            "last successful run of a task",
+        );
+        for ((account, task), at) in &inner.last_success {
+            let _ = writeln!(
+                out,
+                "athene_task_last_success_timestamp_seconds{{account=\"{}\",task=\"{}\"}} {}",
+                escape(account),
+                task,
+                at
+            );
+        }
+
+        for (name, help) in [
+            ("athene_number_gem", "gem of the latest tap data"),
+            ("athene_number_ec", "gold of the latest tap data"),
+            ("athene_level", "level of the latest tap data"),
+        ] {
+            header(&mut out, name, "gauge", help);
+            for (account, (gem, ec, level)) in &inner.tap {
+                let value = match name {
+                    "athene_number_gem" => gem.to_string(),
+                    "athene_number_ec" => ec.to_string(),
+                    _ => level.to_string(),
+                };
+                let _ = writeln!(out, "{}{{account=\"{}\"}} {}", name, escape(account), value);
+            }
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Label value with `\`, `"` and newlines escaped.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves `metrics` on `/metrics` of `listener` until the handle is
+/// aborted.
+pub fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> std::io::Result<JoinHandle<()>> {
+    info!("metrics on http://{}/metrics", listener.local_addr()?);
+    let app = Router::new().route(
+        "/metrics",
+        get(move || async move {
+            (
+                [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+                metrics.render(),
+            )
+        }),
+    );
+    Ok(tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("metrics server: {}", e);
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_every_metric() {
+        let metrics = Metrics::default();
+        metrics.request(
+            "alice",
+            "get-tap-earn",
+            Some(200),
+            Duration::from_millis(80),
+        );
+        metrics.request("alice", "get-tap-earn", None, Duration::from_secs(3));
+        metrics.task_run("alice", "claim_gem", true, 1_500);
+        metrics.task_run("alice", "claim_gem", false, 2_000);
+        metrics.task_succeeded_at("alice", "claim_gem", 1_000);
+        metrics.tap(
+            "alice",
+            &TapData {
+                number_gem: 1.5,
+                number_ec: 300,
+                level: 4,
+                base_rate: 1.0,
+                min_ec: 100,
+                number_tap: 0,
+            },
+        );
+
+        let out = metrics.render();
+        for line in [
+            r#"athene_requests_total{account="alice",endpoint="get-tap-earn",status="200"} 1"#,
+            r#"athene_requests_total{account="alice",endpoint="get-tap-earn",status="error"} 1"#,
+            r#"athene_request_duration_seconds_bucket{account="alice",endpoint="get-tap-earn",le="0.05"} 0"#,
+            r#"athene_request_duration_seconds_bucket{account="alice",endpoint="get-tap-earn",le="0.1"} 1"#,
+            r#"athene_request_duration_seconds_bucket{account="alice",endpoint="get-tap-earn",le="5"} 2"#,
+            r#"athene_request_duration_seconds_bucket{account="alice",endpoint="get-tap-earn",le="+Inf"} 2"#,
+            r#"athene_request_duration_seconds_count{account="alice",endpoint="get-tap-earn"} 2"#,
+            r#"athene_task_runs_total{account="alice",task="claim_gem",result="success"} 1"#,
+            r#"athene_task_runs_total{account="alice",task="claim_gem",result="failure"} 1"#,
+            // a run wins over the record of the last restart
+            r#"athene_task_last_success_timestamp_seconds{account="alice",task="claim_gem"} 1.5"#,
+            r#"athene_number_gem{account="alice"} 1.5"#,
+            r#"athene_number_ec{account="alice"} 300"#,
+            r#"athene_level{account="alice"} 4"#,
+        ] {
+            assert!(out.lines().any(|l| l == line), "{}\n{}", line, out);
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_metrics_over_http() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.task_run("alice", "check_in", true, 1_000);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(listener, metrics).unwrap();
+
+        let body = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains(
+            r#"athene_task_runs_total{account="alice",task="check_in",result="success"} 1"#
+        ));
+        server.abort();
+    }
+}
@@ -0,0 +1,412 @@
+//! Stateful stand-in for the miniapp and auth api, served on localhost so
+//! tests can run the real jobs end to end without network.
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::{Oaep, RsaPrivateKey};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::accounts::{self, AccountStore};
+use crate::api::AtheneClient;
+use crate::config::Settings;
+use crate::events::Events;
+use crate::tasks::AccountContext;
+use crate::transport::fake::FakeTransport;
+use crate::utils;
+
+pub const MOCK_PRIVATE_KEY: &str = include_str!("../tests/fixtures/mock_private_key.pem");
+pub const MOCK_PUBLIC_KEY: &str = include_str!("../tests/fixtures/mock_public_key.pem");
+
+/// Link as grabbed from the telegram web app, its init data is accepted by
+/// the mock login.
+pub const MOCK_LINK: &str =
+    "https://miniapp.athene.network/#tgWebAppData=query_id%3DAAE%26user%3D42%26hash%3Dabc&tgWebAppVersion=7.6";
+
+#[derive(Debug, Clone)]
+pub struct MockQuest {
+    pub group: &'static str,
+    pub id: i64,
+    pub status: &'static str,
+}
+
+#[derive(Debug)]
+pub struct MockState {
+    pub issued_tokens: HashSet<String>,
+    pub valid_tokens: HashSet<String>,
+    pub logins: u32,
+    pub number_gem: f64,
+    pub number_ec: i64,
+    pub level: i64,
+    pub base_rate: f64,
+    pub min_ec: i64,
+    pub number_tap: i64,
+    pub remain_time_next_claim: i64,
+    pub check_ins: u32,
+    pub gem_claims: u32,
+    /// amounts accepted by post-convert-gem
+    pub conversions: Vec<i64>,
+    /// (name, canClaim)
+    pub packages: Vec<(String, bool)>,
+    pub claimed_packages: Vec<String>,
+    pub quests: Vec<MockQuest>,
+    pub claimed_quests: Vec<i64>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        let min_ec = 1000;
+        MockState {
+            issued_tokens: HashSet::new(),
+            valid_tokens: HashSet::new(),
+            logins: 0,
+            number_gem: 0.0,
+            number_ec: 0,
+            level: 1,
+            base_rate: 0.001,
+            min_ec,
+            // enough taps accumulated for one conversion
+            number_tap: utils::get_current_timestamp() - (min_ec + 200) * 100,
+            remain_time_next_claim: 0,
+            check_ins: 0,
+            gem_claims: 0,
+            conversions: Vec::new(),
+            packages: vec![("daily".to_string(), true), ("weekly".to_string(), false)],
+            claimed_packages: Vec::new(),
+            quests: vec![
+                MockQuest {
+                    group: "daily",
+                    id: 1,
+                    status: "claimable",
+                },
+                MockQuest {
+                    group: "weekly",
+                    id: 2,
+                    status: "claimed",
+                },
+            ],
+            claimed_quests: Vec::new(),
+        }
+    }
+}
+
+impl MockState {
+    /// Invalidates every token handed out so far.
+    pub fn expire_tokens(&mut self) {
+        self.valid_tokens.clear();
+    }
+}
+
+struct Mock {
+    state: Mutex<MockState>,
+    private_key: RsaPrivateKey,
+}
+
+pub struct MockServer {
+    pub base: String,
+    mock: Arc<Mock>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start(state: MockState) -> MockServer {
+        let mock = Arc::new(Mock {
+            state: Mutex::new(state),
+            private_key: RsaPrivateKey::from_pkcs8_pem(MOCK_PRIVATE_KEY).unwrap(),
+        });
+        let app = Router::new()
+            .route("/api/v1.0/auth/login-telegram", post(login_telegram))
+            .route("/api/login", post(login))
+            .route("/api/get-tap-earn", get(get_tap_earn))
+            .route("/api/post-convert-gem", post(post_convert_gem))
+            .route("/api/get-mining", get(get_mining))
+            .route("/api/post-claim-gem", post(post_claim_gem))
+            .route("/api/post-check-in", post(post_check_in))
+            .route("/api/get-premium-pick/", get(get_premium_pick))
+            .route("/api/post-premium-pick/", post(post_premium_pick))
+            .route("/api/get-list-quest/", get(get_list_quest))
+            .route("/api/post-quest-reward/", post(post_quest_reward))
+            .with_state(mock.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        MockServer { base, mock, handle }
+    }
+
+    /// Settings pointing every request at this server.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            api_base: self.base.clone(),
+            auth_base: self.base.clone(),
+            convert_public_key: MOCK_PUBLIC_KEY.to_string(),
+            pace: 0.0,
+            ..Settings::default()
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.mock.state.lock().unwrap()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// `user.json` with one account, `alice`, that has to login first.
+pub fn user_json(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("athene-bot-{}-{}", std::process::id(), test));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("user.json");
+    let users = json!({
+        "alice": { "link": MOCK_LINK, "access_token": null, "invite_code": "38f721dc95aa" }
+    });
+    std::fs::write(&file_path, users.to_string()).unwrap();
+    file_path
+}
+
+/// `alice` logged in against a fresh mock.
+pub struct MockAccount {
+    pub mock: MockServer,
+    pub store: Arc<AccountStore>,
+    pub ctx: AccountContext,
+    /// `user.json` behind `store`
+    pub users_file: PathBuf,
+}
+
+pub async fn mock_account(test: &str) -> MockAccount {
+    let mock = MockServer::start(MockState::default()).await;
+    let users_file = user_json(test);
+    let store = Arc::new(AccountStore::load(&users_file).unwrap());
+    let user = store.users().remove("alice").unwrap();
+    let settings = mock.settings();
+    let client = accounts::init_client(
+        "alice",
+        &user,
+        &settings,
+        store.clone(),
+        &Events::default(),
+        &Arc::default(),
+    )
+    .await
+    .unwrap();
+    let ctx = AccountContext::new(client, user, Arc::new(settings));
+    MockAccount {
+        mock,
+        store,
+        ctx,
+        users_file,
+    }
+}
+
+/// Account `test` with a token, answered by `transport`.
+pub fn fake_context(transport: Arc<FakeTransport>) -> AccountContext {
+    let settings = Settings {
+        pace: 0.0,
+        ..Settings::default()
+    };
+    let client = AtheneClient::with_transport("test", &settings, transport);
+    client.set_token("token");
+    let user = serde_json::from_value(
+        json!({ "link": null, "access_token": "token", "invite_code": null }),
+    )
+    .unwrap();
+    AccountContext::new(Arc::new(client), user, Arc::new(settings))
+}
+
+type Shared = State<Arc<Mock>>;
+
+fn ok(data: Value) -> Response {
+    Json(json!({ "message": "ok", "data": data })).into_response()
+}
+
+fn fail(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "message": message }))).into_response()
+}
+
+/// Locks the state when the `token` cookie is a valid one.
+fn authorized<'a>(mock: &'a Mock, headers: &HeaderMap) -> Option<MutexGuard<'a, MockState>> {
+    let token = headers
+        .get("cookie")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("token="))
+        .unwrap_or_default();
+    let state = mock.state.lock().unwrap();
+    state.valid_tokens.contains(token).then_some(state)
+}
+
+async fn login_telegram(State(mock): Shared, Json(body): Json<Value>) -> Response {
+    let decoded = body["token"]
+        .as_str()
+        .and_then(|t| STANDARD.decode(t).ok())
+        .and_then(|t| String::from_utf8(t).ok())
+        .unwrap_or_default();
+    if !decoded.starts_with("query_id=") || !decoded.contains("&invite_code=") {
+        return fail(StatusCode::BAD_REQUEST, "invalid telegram data");
+    }
+    let mut state = mock.state.lock().unwrap();
+    state.logins += 1;
+    let token = format!("mock-token-{}", state.logins);
+    state.issued_tokens.insert(token.clone());
+    ok(json!({ "accessToken": token }))
+}
+
+async fn login(State(mock): Shared, Json(body): Json<Value>) -> Response {
+    let token = body["token"].as_str().unwrap_or_default();
+    let mut state = mock.state.lock().unwrap();
+    if !state.issued_tokens.contains(token) {
+        return fail(StatusCode::UNAUTHORIZED, "unknown token");
+    }
+    state.valid_tokens.insert(token.to_string());
+    ok(json!({}))
+}
+
+async fn get_tap_earn(State(mock): Shared, headers: HeaderMap) -> Response {
+    let Some(state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    ok(json!({
+        "numberGem": state.number_gem,
+        "numberEc": state.number_ec,
+        "level": state.level,
+        "baseRate": state.base_rate,
+        "minEc": state.min_ec,
+        "numberTap": state.number_tap,
+    }))
+}
+
+async fn post_convert_gem(
+    State(mock): Shared,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let payload = body["encrypt"]
+        .as_str()
+        .and_then(|e| STANDARD.decode(e).ok())
+        .and_then(|e| mock.private_key.decrypt(Oaep::new::<Sha256>(), &e).ok())
+        .and_then(|p| String::from_utf8(p).ok());
+    let Some(mut state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    // `{number_tap}-{timestamp}-{amount}`
+    let parts: Vec<i64> = payload
+        .unwrap_or_default()
+        .split('-')
+        .filter_map(|p| p.parse().ok())
+        .collect();
+    let [number_tap, ts, amount] = parts[..] else {
+        return fail(StatusCode::BAD_REQUEST, "invalid payload");
+    };
+    let earned = (ts - number_tap) / 100 - 100;
+    if number_tap != state.number_tap || amount < state.min_ec || amount > earned {
+        return fail(StatusCode::BAD_REQUEST, "invalid amount");
+    }
+    state.number_tap = ts;
+    state.number_gem += amount as f64 * state.base_rate;
+    state.conversions.push(amount);
+    ok(json!({}))
+}
+
+async fn get_mining(State(mock): Shared, headers: HeaderMap) -> Response {
+    let Some(state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    ok(json!({ "remainTimeNextClaim": state.remain_time_next_claim }))
+}
+
+async fn post_claim_gem(State(mock): Shared, headers: HeaderMap) -> Response {
+    let Some(mut state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    if state.remain_time_next_claim > 0 {
+        return fail(StatusCode::BAD_REQUEST, "not yet");
+    }
+    state.gem_claims += 1;
+    state.number_gem += 1.0;
+    state.remain_time_next_claim = 6 * 60 * 60;
+    ok(json!({}))
+}
+
+async fn post_check_in(State(mock): Shared, headers: HeaderMap) -> Response {
+    let Some(mut state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    state.check_ins += 1;
+    ok(json!({}))
+}
+
+async fn get_premium_pick(State(mock): Shared, headers: HeaderMap) -> Response {
+    let Some(state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    let packages: Vec<Value> = state
+        .packages
+        .iter()
+        .map(|(name, can_claim)| json!({ "name": name, "canClaim": can_claim }))
+        .collect();
+    ok(json!({ "totalReward": 10, "packages": packages }))
+}
+
+async fn post_premium_pick(
+    State(mock): Shared,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(mut state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    let name = body["packageName"].as_str().unwrap_or_default().to_string();
+    match state.packages.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, can_claim)) if *can_claim => *can_claim = false,
+        _ => return fail(StatusCode::BAD_REQUEST, "not claimable"),
+    }
+    state.claimed_packages.push(name);
+    ok(json!({}))
+}
+
+async fn get_list_quest(State(mock): Shared, headers: HeaderMap) -> Response {
+    let Some(state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    let mut data = json!({ "daily": [], "event": [], "top": [], "weekly": [] });
+    for quest in &state.quests {
+        data[quest.group]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "id": quest.id, "status": quest.status }));
+    }
+    ok(data)
+}
+
+async fn post_quest_reward(
+    State(mock): Shared,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let Some(mut state) = authorized(&mock, &headers) else {
+        return fail(StatusCode::UNAUTHORIZED, "unauthorized");
+    };
+    let id = body["quest"].as_i64().unwrap_or_default();
+    match state.quests.iter_mut().find(|q| q.id == id) {
+        Some(quest) if quest.status == "claimable" => quest.status = "claimed",
+        _ => return fail(StatusCode::BAD_REQUEST, "not claimable"),
+    }
+    state.claimed_quests.push(id);
+    ok(json!({}))
+}
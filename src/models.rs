@@ -0,0 +1,200 @@
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Deserialize;
+
+use crate::api::TapData;
+use crate::error::{AtheneError, ErrorKind};
+
+/// `{ "message": "ok", "data": { ... } }` wrapper shared by the miniapp api.
+#[derive(Deserialize, Debug)]
+pub struct ApiEnvelope<T> {
+    pub message: Option<String>,
+    pub data: Option<T>,
+}
+
+impl<T> ApiEnvelope<T> {
+    pub fn into_data(self, endpoint: &'static str, body: &str) -> Result<T, AtheneError> {
+        match (self.message.as_deref(), self.data) {
+            (Some("ok"), Some(data)) => Ok(data),
+            (Some("ok"), None) => Err(AtheneError::decode(endpoint, "missing `data` field", body)),
+            (message, _) => Err(AtheneError::new(
+                ErrorKind::Rejected,
+                endpoint,
+                format!("message: {:?}", message),
+            )
+            .with_body(body)),
+        }
+    }
+}
+
+/// Decodes `body` as `ApiEnvelope<T>` and unwraps its data.
+pub fn decode_data<T: DeserializeOwned>(
+    endpoint: &'static str,
+    body: &str,
+) -> Result<T, AtheneError> {
+    let envelope: ApiEnvelope<T> = serde_json::from_str(body)
+        .map_err(|e| AtheneError::decode(endpoint, e.to_string(), body))?;
+    envelope.into_data(endpoint, body)
+}
+
+/// Checks that `body` is an envelope whose `message` is `ok`, whatever its
+/// data, for the calls that only act.
+pub fn check_message(endpoint: &'static str, body: &str) -> Result<(), AtheneError> {
+    let envelope: ApiEnvelope<IgnoredAny> = serde_json::from_str(body)
+        .map_err(|e| AtheneError::decode(endpoint, e.to_string(), body))?;
+    match envelope.message.as_deref() {
+        Some("ok") => Ok(()),
+        message => Err(AtheneError::new(
+            ErrorKind::Rejected,
+            endpoint,
+            format!("message: {:?}", message),
+        )
+        .with_body(body)),
+    }
+}
+
+/// Whether `body` is an envelope whose `message` says the token is no
+/// longer valid, which some endpoints answer with a 200. Other refusals,
+/// such as an expired quest, are left to `check_message`.
+pub fn is_token_expired(body: &str) -> bool {
+    let Ok(envelope) = serde_json::from_str::<ApiEnvelope<IgnoredAny>>(body) else {
+        return false;
+    };
+    let message = envelope.message.unwrap_or_default().trim().to_lowercase();
+    matches!(message.as_str(), "unauthorized" | "unauthenticated")
+        || [
+            "token expired",
+            "expired token",
+            "token has expired",
+            "token is expired",
+            "invalid token",
+            "token is invalid",
+            "jwt expired",
+            "jwt malformed",
+        ]
+        .iter()
+        .any(|m| message.contains(m))
+}
+
+/** /api/get-tap-earn */
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TapEarn {
+    pub number_gem: f64,
+    pub number_ec: i64,
+    pub level: i64,
+    pub base_rate: f64,
+    pub min_ec: i64,
+    pub number_tap: i64,
+}
+
+impl From<TapEarn> for TapData {
+    fn from(t: TapEarn) -> Self {
+        TapData {
+            number_gem: t.number_gem as f32,
+            number_ec: t.number_ec as i32,
+            level: t.level as i32,
+            base_rate: t.base_rate as f32,
+            min_ec: t.min_ec as i32,
+            number_tap: t.number_tap,
+        }
+    }
+}
+
+/** /api/get-mining */
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Mining {
+    /// seconds until the gem can be claimed
+    #[serde(default)]
+    pub remain_time_next_claim: Option<i64>,
+}
+
+/** /api/get-premium-pick */
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PremiumPick {
+    pub total_reward: i64,
+    #[serde(default)]
+    pub packages: Vec<PremiumPackage>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PremiumPackage {
+    pub name: String,
+    pub can_claim: bool,
+}
+
+/** /api/get-list-quest */
+#[derive(Deserialize, Debug)]
+pub struct QuestList {
+    #[serde(default)]
+    pub daily: Vec<Quest>,
+    #[serde(default)]
+    pub event: Vec<Quest>,
+    #[serde(default)]
+    pub top: Vec<Quest>,
+    #[serde(default)]
+    pub weekly: Vec<Quest>,
+}
+
+impl QuestList {
+    /// Every group in the order the bot claims them: daily, event, top, weekly.
+    pub fn all(&self) -> impl Iterator<Item = &Quest> {
+        self.daily
+            .iter()
+            .chain(self.event.iter())
+            .chain(self.top.iter())
+            .chain(self.weekly.iter())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Quest {
+    pub id: i64,
+    pub status: String,
+}
+
+impl Quest {
+    pub fn is_claimable(&self) -> bool {
+        self.status == "claimable"
+    }
+}
+
+/** athene.network /api/v1.0/auth/login-telegram */
+#[derive(Deserialize, Debug)]
+pub struct LoginTelegramResponse {
+    pub data: LoginTelegram,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginTelegram {
+    pub access_token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_token_messages_mean_an_expired_token() {
+        for body in [
+            r#"{"message":"Token expired"}"#,
+            r#"{"message":"invalid token"}"#,
+            r#"{"message":"jwt expired"}"#,
+            r#"{"message":"Unauthorized"}"#,
+        ] {
+            assert!(is_token_expired(body), "{}", body);
+        }
+        for body in [
+            r#"{"message":"ok","data":{}}"#,
+            r#"{"message":"quest expired"}"#,
+            r#"{"message":"event has expired"}"#,
+            r#"{"message":"unauthorized quest"}"#,
+            "not json",
+        ] {
+            assert!(!is_token_expired(body), "{}", body);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Masks secrets in log lines and error messages: access tokens, cookies,
+//! telegram init data and its hash. On unless `disable_unsafe` is called.
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Logs secrets in clear from now on, for local debugging only.
+pub fn disable_unsafe() {
+    DISABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    !DISABLED.load(Ordering::SeqCst)
+}
+
+fn patterns() -> &'static [Regex; 2] {
+    static PATTERNS: OnceLock<[Regex; 2]> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            // `token=` of cookies and urls, the init data pairs, raw or
+            // url-encoded in `tgWebAppData`
+            Regex::new(
+                r"(?i)((?:access_token|token|tgWebAppData|query_id|user|hash|signature)(?:=|%3D))[^;&\s\x22\\]+",
+            )
+            .unwrap(),
+            // json keys, quotes escaped once more by every `{:?}` or json
+            // log line around them
+            Regex::new(r#"(?i)(\\*"(?:access_?token|token|link|cookie)\\*"\s*:\s*\\*")[^"\\]*"#)
+                .unwrap(),
+        ]
+    })
+}
+
+/// `s` with every secret replaced by `***`.
+pub fn redact(s: &str) -> Cow<'_, str> {
+    if !is_enabled() {
+        return Cow::Borrowed(s);
+    }
+    let mut s = Cow::Borrowed(s);
+    for pattern in patterns() {
+        if let Cow::Owned(masked) = pattern.replace_all(&s, "${1}***") {
+            s = Cow::Owned(masked);
+        }
+    }
+    s
+}
+
+/// Writer masking secrets in what goes through it, one log line per
+/// `write`, e.g. `Redacted(std::io::stderr())`.
+pub struct Redacted<W>(pub W);
+
+impl<W: Write> Write for Redacted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(line) => self.0.write_all(redact(line).as_bytes())?,
+            Err(_) => self.0.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_tokens_and_init_data() {
+        assert_eq!(
+            redact("cookie: token=abc.def; lang=en"),
+            "cookie: token=***; lang=en"
+        );
+        assert_eq!(
+            redact(r#"{"message":"ok","data":{"accessToken":"eyJhbGci"}}"#),
+            r#"{"message":"ok","data":{"accessToken":"***"}}"#
+        );
+        assert_eq!(
+            redact(r#"{"token":"cXVlcnlfaWQ9","access_token":null}"#),
+            r#"{"token":"***","access_token":null}"#
+        );
+        assert_eq!(
+            redact("https://miniapp.athene.network/#tgWebAppData=query_id%3DAAE%26hash%3Dabc&tgWebAppVersion=7.6"),
+            "https://miniapp.athene.network/#tgWebAppData=***&tgWebAppVersion=7.6"
+        );
+        assert_eq!(
+            redact("query_id=AAE&user=%7B%22id%22%3A42%7D&auth_date=1&hash=9f86d081"),
+            "query_id=***&user=***&auth_date=1&hash=***"
+        );
+        // a `{:?}` body inside a json log line
+        assert_eq!(
+            redact(r#"{"message":"body: \"{\\\"accessToken\\\":\\\"eyJ\\\"}\""}"#),
+            r#"{"message":"body: \"{\\\"accessToken\\\":\\\"***\\\"}\""}"#
+        );
+        assert_eq!(redact("get-tap-earn: ok"), "get-tap-earn: ok");
+    }
+
+    #[test]
+    fn writer_masks_each_line() {
+        let mut out = Redacted(Vec::new());
+        out.write_all(b"login token=secret\n").unwrap();
+        assert_eq!(out.0, b"login token=***\n");
+    }
+}
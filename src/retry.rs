@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use crate::config::RetryPolicy;
+use crate::error::{AtheneError, ErrorKind};
+
+/// Whether an endpoint can be sent again after a failure that may have
+/// reached the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotency {
+    /// reads, and claims the server only grants once
+    Safe,
+    /// repeating it could apply the change twice
+    Unsafe,
+}
+
+pub fn idempotency(endpoint: &str) -> Idempotency {
+    match endpoint {
+        // the encrypted payload is replayable, a duplicate could convert twice
+        "post-convert-gem" => Idempotency::Unsafe,
+        _ => Idempotency::Safe,
+    }
+}
+
+/// How long to wait before attempt `attempt + 1`, or `None` when `err`
+/// should not be retried.
+pub fn retry_delay(
+    endpoint: &str,
+    err: &AtheneError,
+    policy: &RetryPolicy,
+    attempt: u32,
+) -> Option<Duration> {
+    if attempt >= policy.max_attempts {
+        return None;
+    }
+    let retryable = match err.kind {
+        // a 429 or a refused connection means the server did not act on it
+        ErrorKind::RateLimited => true,
+        ErrorKind::Transport if !err.request_sent => true,
+        ErrorKind::Server | ErrorKind::Transport => idempotency(endpoint) == Idempotency::Safe,
+        ErrorKind::Unauthorized | ErrorKind::Decode | ErrorKind::Rejected | ErrorKind::Encrypt => {
+            false
+        }
+    };
+    if !retryable {
+        return None;
+    }
+    match err.retry_after {
+        // do not come back sooner than asked, give up if that is too far away
+        Some(after) if after > policy.max_delay() => None,
+        Some(after) => Some(after),
+        None => Some(policy.backoff(attempt)),
+    }
+}
+
+/// Parses a `Retry-After` value, either delay seconds or an http date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetrySettings;
+    use crate::transport::TransportError;
+    use reqwest::StatusCode;
+
+    fn status(code: u16) -> AtheneError {
+        AtheneError::from_status("get-tap-earn", StatusCode::from_u16(code).unwrap(), "")
+    }
+
+    fn timeout(endpoint: &'static str) -> AtheneError {
+        AtheneError::transport(
+            endpoint,
+            TransportError {
+                message: "operation timed out".to_string(),
+                connect: false,
+            },
+        )
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay_ms: 500,
+            max_delay_ms: 3_000,
+        };
+        let delays: Vec<_> = (1..=5).map(|a| policy.backoff(a).as_millis()).collect();
+        assert_eq!(delays, [500, 1_000, 2_000, 3_000, 3_000]);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn honors_retry_after_within_max_delay() {
+        let policy = RetryPolicy::default();
+        let limited = |secs| status(429).with_retry_after(Some(Duration::from_secs(secs)));
+        assert_eq!(
+            retry_delay("get-tap-earn", &limited(2), &policy, 1),
+            Some(Duration::from_secs(2))
+        );
+        // longer than the policy allows to wait: give up
+        assert_eq!(retry_delay("get-tap-earn", &limited(60), &policy, 1), None);
+        // without the header, the usual backoff
+        assert_eq!(
+            retry_delay("get-tap-earn", &status(429), &policy, 2),
+            Some(policy.backoff(2))
+        );
+        assert_eq!(retry_delay("get-tap-earn", &limited(2), &policy, 3), None);
+    }
+
+    #[test]
+    fn retries_only_what_is_safe_to_repeat() {
+        let policy = RetryPolicy::default();
+        assert!(retry_delay("get-tap-earn", &status(502), &policy, 1).is_some());
+        assert!(retry_delay("get-tap-earn", &timeout("get-tap-earn"), &policy, 1).is_some());
+        assert!(retry_delay("get-tap-earn", &status(400), &policy, 1).is_none());
+        assert!(retry_delay("get-tap-earn", &status(401), &policy, 1).is_none());
+
+        // the conversion may have gone through
+        let convert = timeout("post-convert-gem");
+        assert_eq!(retry_delay("post-convert-gem", &convert, &policy, 1), None);
+        let mut refused = convert;
+        refused.request_sent = false;
+        assert!(retry_delay("post-convert-gem", &refused, &policy, 1).is_some());
+    }
+
+    #[test]
+    fn endpoints_override_the_default_policy() {
+        let settings: RetrySettings = serde_json::from_str(
+            r#"{"max_attempts": 2, "endpoints": {"post-claim-gem": {"max_attempts": 5}}}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.policy("get-tap-earn").max_attempts, 2);
+        assert_eq!(settings.policy("post-claim-gem").max_attempts, 5);
+        assert_eq!(settings.policy("post-claim-gem").base_delay_ms, 500);
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+        let at = Utc::now() + chrono::Duration::seconds(120);
+        let delay = parse_retry_after(&at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).unwrap();
+        assert!(delay > Duration::from_secs(110) && delay <= Duration::from_secs(120));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
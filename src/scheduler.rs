@@ -0,0 +1,929 @@
+use chrono::{DateTime, Local};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_cron_scheduler::{Job, JobScheduler, JobSchedulerError};
+use tracing::{info, info_span, Instrument};
+
+use crate::events::{BotEvent, Events};
+use crate::metrics::Metrics;
+use crate::state::{self, StateStore, TaskRecord};
+use crate::tasks::{AccountContext, Task, TaskOutcome, TaskRegistry};
+use crate::utils;
+
+/// When a task runs, written as a string in the config:
+/// - `"auto"`: when the server says the task is due, claim and convert only
+/// - `"every 6h"`: fixed interval, with a `s`, `m`, `h` or `d` unit
+/// - anything else is a cron expression with seconds, in UTC,
+///   e.g. `"0 30 */6 * * *"`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TaskSchedule {
+    Auto,
+    Every(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl TryFrom<String> for TaskSchedule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let s = s.trim();
+        if s == "auto" {
+            return Ok(TaskSchedule::Auto);
+        }
+        if let Some(every) = s.strip_prefix("every ") {
+            return parse_interval(every.trim())
+                .map(TaskSchedule::Every)
+                .ok_or_else(|| format!("invalid interval {:?}, e.g. \"every 6h\"", s));
+        }
+        cron::Schedule::from_str(s)
+            .map(|cron| TaskSchedule::Cron(Box::new(cron)))
+            .map_err(|e| format!("invalid cron expression {:?}: {}", s, e))
+    }
+}
+
+/// `30s`, `10m`, `6h` or `1d`, never zero.
+fn parse_interval(s: &str) -> Option<Duration> {
+    let unit = match s.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    let n: u64 = s[..s.len() - 1].parse().ok()?;
+    if n == 0 {
+        return None;
+    }
+    n.checked_mul(unit).map(Duration::from_secs)
+}
+
+impl Display for TaskSchedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskSchedule::Auto => write!(f, "auto"),
+            TaskSchedule::Every(every) => write!(f, "every {}s", every.as_secs()),
+            TaskSchedule::Cron(cron) => write!(f, "{}", cron),
+        }
+    }
+}
+
+impl From<TaskSchedule> for String {
+    fn from(schedule: TaskSchedule) -> Self {
+        schedule.to_string()
+    }
+}
+
+/// Counts the jobs currently running so shutdown can wait for them, and
+/// refuses new ones once closed.
+#[derive(Default)]
+pub struct InFlight {
+    /// (running jobs, closed)
+    state: Mutex<(usize, bool)>,
+    idle: Notify,
+}
+
+pub struct InFlightGuard {
+    in_flight: Arc<InFlight>,
+}
+
+impl InFlight {
+    /// Registers a running job, `None` once shutting down.
+    pub fn enter(self: &Arc<Self>) -> Option<InFlightGuard> {
+        let mut state = self.state.lock().unwrap();
+        if state.1 {
+            return None;
+        }
+        state.0 += 1;
+        Some(InFlightGuard {
+            in_flight: self.clone(),
+        })
+    }
+
+    pub fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().1
+    }
+
+    pub async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            if self.state.lock().unwrap().0 == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut state = self.in_flight.state.lock().unwrap();
+        state.0 -= 1;
+        if state.0 == 0 {
+            self.in_flight.idle.notify_waiters();
+        }
+    }
+}
+
+/// Tasks running right now, by account and task name, so the same task
+/// never runs twice at once for an account.
+#[derive(Default)]
+pub struct Running {
+    tasks: Mutex<HashSet<(String, &'static str)>>,
+}
+
+pub struct RunningGuard {
+    running: Arc<Running>,
+    key: (String, &'static str),
+}
+
+impl Running {
+    /// Marks `task` of `account` as running, `None` when it already is.
+    pub fn try_start(self: &Arc<Self>, account: &str, task: &'static str) -> Option<RunningGuard> {
+        let key = (account.to_string(), task);
+        if !self.tasks.lock().unwrap().insert(key.clone()) {
+            return None;
+        }
+        Some(RunningGuard {
+            running: self.clone(),
+            key,
+        })
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.running.tasks.lock().unwrap().remove(&self.key);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TaskStatus {
+    pub runs: u64,
+    pub errors: u64,
+    /// runs dropped because the previous one was still going
+    pub skips: u64,
+    pub last_run: Option<DateTime<Local>>,
+    /// error of the last run, `None` when it went fine
+    pub last_error: Option<String>,
+}
+
+/// Status of every task, by account and task name.
+#[derive(Default)]
+pub struct TaskStats {
+    tasks: Mutex<BTreeMap<(String, &'static str), TaskStatus>>,
+}
+
+impl TaskStats {
+    pub fn record(&self, account: &str, task: &'static str, outcome: &TaskOutcome) -> TaskStatus {
+        let mut tasks = self.tasks.lock().unwrap();
+        let status = tasks.entry((account.to_string(), task)).or_default();
+        status.runs += 1;
+        status.last_run = Some(Local::now());
+        status.last_error = outcome.result.as_ref().err().map(|e| e.to_string());
+        if status.last_error.is_some() {
+            status.errors += 1;
+        }
+        status.clone()
+    }
+
+    pub fn skip(&self, account: &str, task: &'static str) -> TaskStatus {
+        let mut tasks = self.tasks.lock().unwrap();
+        let status = tasks.entry((account.to_string(), task)).or_default();
+        status.skips += 1;
+        status.clone()
+    }
+
+    pub fn snapshot(&self) -> Vec<((String, &'static str), TaskStatus)> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
+
+/// Shared by every scheduled task.
+#[derive(Clone, Default)]
+pub struct Runner {
+    pub events: Events,
+    pub metrics: Arc<Metrics>,
+    pub in_flight: Arc<InFlight>,
+    pub running: Arc<Running>,
+    pub stats: Arc<TaskStats>,
+    pub state: Arc<StateStore>,
+}
+
+impl Runner {
+    /// Runs `task` unless the bot is shutting down or its previous run for
+    /// `account` is still going, and records how it went. Returns when it
+    /// wants to run again.
+    pub async fn run(
+        &self,
+        account: &str,
+        task: &'static str,
+        job: impl Future<Output = TaskOutcome>,
+    ) -> Option<Duration> {
+        self.run_outcome(account, task, job).await?.next_run
+    }
+
+    /// Same as `run`, returns the whole outcome, `None` when it did not run.
+    pub async fn run_outcome(
+        &self,
+        account: &str,
+        task: &'static str,
+        job: impl Future<Output = TaskOutcome>,
+    ) -> Option<TaskOutcome> {
+        let _guard = self.in_flight.enter()?;
+        let Some(_running) = self.running.try_start(account, task) else {
+            // `job` is dropped before it sent anything
+            let status = self.stats.skip(account, task);
+            self.events.emit(
+                account,
+                BotEvent::Warning(format!(
+                    "{}: skipped, previous run still going, skips: {}",
+                    task, status.skips
+                )),
+            );
+            return None;
+        };
+        let outcome = job.instrument(info_span!("task", account, task)).await;
+        let status = self.stats.record(account, task, &outcome);
+        let now_ms = utils::get_current_timestamp();
+        self.state.record(account, task, now_ms, &outcome);
+        self.metrics
+            .task_run(account, task, outcome.result.is_ok(), now_ms);
+        match &outcome.result {
+            Ok(()) => self.events.emit(
+                account,
+                BotEvent::Info(format!(
+                    "{}: ok, runs: {}, errors: {}",
+                    task, status.runs, status.errors
+                )),
+            ),
+            Err(err) => self.events.emit(
+                account,
+                BotEvent::TaskFailed {
+                    task,
+                    error: err.to_string(),
+                    runs: status.runs,
+                    errors: status.errors,
+                },
+            ),
+        }
+        Some(outcome)
+    }
+}
+
+/// When a task first runs once the account is scheduled.
+#[derive(Debug, PartialEq)]
+enum FirstRun {
+    /// right away, before the next task of the account is scheduled
+    Now,
+    /// once after this delay, then on schedule
+    After(Duration),
+    /// only when the schedule says so
+    OnSchedule,
+}
+
+/// First run of a task that ran before the restart, from its last record.
+/// A run missed while the bot was down, or a failed last run, is caught up
+/// right away.
+fn resume(schedule: &TaskSchedule, record: &TaskRecord, now_ms: i64) -> FirstRun {
+    match schedule {
+        TaskSchedule::Auto => match record.next_run {
+            Some(at) => FirstRun::After(state::until(at, now_ms)),
+            None => FirstRun::OnSchedule,
+        },
+        TaskSchedule::Every(every) => match record.last_success {
+            Some(at) => FirstRun::After(state::until(
+                at.saturating_add(every.as_millis() as i64),
+                now_ms,
+            )),
+            None => FirstRun::After(Duration::ZERO),
+        },
+        TaskSchedule::Cron(cron) => {
+            let missed = record.last_success.is_none_or(|at| {
+                DateTime::from_timestamp_millis(at)
+                    .and_then(|at| cron.after(&at).next())
+                    .is_some_and(|next| next.timestamp_millis() <= now_ms)
+            });
+            if missed {
+                FirstRun::After(Duration::ZERO)
+            } else {
+                FirstRun::OnSchedule
+            }
+        }
+    }
+}
+
+/// Adds `task` to `sched` on `schedule`, running it once after `first_run`
+/// when given. An `auto` one without it starts after the task's
+/// `first_run`.
+async fn schedule_task(
+    sched: &JobScheduler,
+    task: Arc<dyn Task>,
+    ctx: Arc<AccountContext>,
+    runner: Runner,
+    schedule: TaskSchedule,
+    first_run: Option<Duration>,
+) -> Result<(), JobSchedulerError> {
+    ctx.client
+        .emit(BotEvent::Info(format!("{}: {}", task.name(), schedule)));
+    if let TaskSchedule::Auto = schedule {
+        let delay = first_run.unwrap_or_else(|| task.first_run());
+        return schedule_once(sched, task, ctx, runner, delay, Then::Returned).await;
+    }
+    let run = {
+        let (task, ctx, runner) = (task.clone(), ctx.clone(), runner.clone());
+        move |_, _| -> BoxFuture<'static, ()> {
+            let task = task.clone();
+            let ctx = ctx.clone();
+            let runner = runner.clone();
+            Box::pin(async move {
+                runner.run(ctx.name(), task.name(), task.run(&ctx)).await;
+            })
+        }
+    };
+    let job = match schedule {
+        TaskSchedule::Every(every) => match first_run {
+            // the interval restarts from the catch-up run
+            Some(delay) => {
+                return schedule_once(sched, task, ctx, runner, delay, Then::Every(every)).await;
+            }
+            None => Job::new_repeated_async(every, run)?,
+        },
+        TaskSchedule::Cron(cron) => {
+            if let Some(delay) = first_run {
+                schedule_once(sched, task, ctx, runner, delay, Then::Nothing).await?;
+            }
+            Job::new_async(*cron, run)?
+        }
+        TaskSchedule::Auto => unreachable!(),
+    };
+    sched.add(job).await?;
+    Ok(())
+}
+
+/// What follows a one-shot run of a task.
+#[derive(Clone, Copy)]
+enum Then {
+    /// nothing, e.g. the cron job is already added
+    Nothing,
+    /// the interval, restarting from this run
+    Every(Duration),
+    /// another one-shot run after the delay this one returned, for `auto`
+    Returned,
+}
+
+/// Runs `task` once after `delay`, then what `then` says unless the bot is
+/// shutting down by then.
+fn schedule_once(
+    sched: &JobScheduler,
+    task: Arc<dyn Task>,
+    ctx: Arc<AccountContext>,
+    runner: Runner,
+    delay: Duration,
+    then: Then,
+) -> BoxFuture<'_, Result<(), JobSchedulerError>> {
+    // boxed, the job schedules the task again
+    Box::pin(async move {
+        ctx.client.emit(BotEvent::Info(format!(
+            "next {} in {:?}",
+            task.name(),
+            delay
+        )));
+        sched
+            .add(Job::new_one_shot_async(delay, move |_, sched| {
+                let task = task.clone();
+                let ctx = ctx.clone();
+                let runner = runner.clone();
+                Box::pin(async move {
+                    let outcome = runner
+                        .run_outcome(ctx.name(), task.name(), task.run(&ctx))
+                        .await;
+                    if runner.in_flight.is_closed() {
+                        return;
+                    }
+                    let next_run = match outcome {
+                        Some(outcome) => outcome.next_run,
+                        // skipped, the run still going won't reschedule it
+                        None => Some(task.retry_after_skip(&ctx)),
+                    };
+                    let result = match (then, next_run) {
+                        (Then::Every(every), _) => {
+                            let schedule = TaskSchedule::Every(every);
+                            schedule_task(&sched, task.clone(), ctx.clone(), runner, schedule, None)
+                                .await
+                        }
+                        (Then::Returned, Some(delay)) => {
+                            schedule_once(&sched, task.clone(), ctx.clone(), runner, delay, then)
+                                .await
+                        }
+                        (Then::Nothing, _) | (Then::Returned, None) => return,
+                    };
+                    if let Err(err) = result {
+                        ctx.client.emit(BotEvent::Warning(format!(
+                            "schedule_{}_error: {}",
+                            task.name(),
+                            err
+                        )));
+                    }
+                })
+            })?)
+            .await?;
+        Ok(())
+    })
+}
+
+/// Schedules every enabled task of `registry` for the account. A task with
+/// a record in the runner's state resumes from it, the others that run at
+/// startup run right away.
+pub async fn schedule_account(
+    sched: &JobScheduler,
+    ctx: Arc<AccountContext>,
+    registry: &TaskRegistry,
+    runner: Runner,
+) -> Result<(), JobSchedulerError> {
+    info!(account = ctx.name(), "start");
+    let now_ms = utils::get_current_timestamp();
+    for task in registry.iter() {
+        if !ctx.is_enabled(task.name()) {
+            ctx.client
+                .emit(BotEvent::Info(format!("{}: disabled", task.name())));
+            continue;
+        }
+        let schedule = ctx.schedule(task.as_ref());
+        let record = runner.state.get(ctx.name(), task.name());
+        if let Some(at_ms) = record.as_ref().and_then(|r| r.last_success) {
+            runner
+                .metrics
+                .task_succeeded_at(ctx.name(), task.name(), at_ms);
+        }
+        let first_run = match record {
+            Some(record) => resume(&schedule, &record, now_ms),
+            None if task.runs_at_startup() => FirstRun::Now,
+            None => FirstRun::OnSchedule,
+        };
+        let first_run = match first_run {
+            FirstRun::Now => {
+                let next_run = runner.run(ctx.name(), task.name(), task.run(&ctx)).await;
+                // an interval or cron restarts from now
+                next_run.filter(|_| matches!(schedule, TaskSchedule::Auto))
+            }
+            FirstRun::After(delay) => Some(delay),
+            FirstRun::OnSchedule => None,
+        };
+        schedule_task(
+            sched,
+            task.clone(),
+            ctx.clone(),
+            runner.clone(),
+            schedule,
+            first_run,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Resolves on ctrl-c, or SIGTERM on unix.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Unable to listen for ctrl-c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Unable to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::AtheneClient;
+    use crate::config::Settings;
+    use crate::mock::{fake_context, mock_account, MockAccount};
+    use crate::tasks::{CheckIn, DailyQuest, PremiumPick};
+    use crate::transport::fake::FakeTransport;
+    use reqwest::Method;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::sync::Semaphore;
+
+    fn parse(s: &str) -> Result<TaskSchedule, String> {
+        TaskSchedule::try_from(s.to_string())
+    }
+
+    /// `auto` task asking to run again 50ms after each run, holding each
+    /// run until `gate` lets it through.
+    struct Tick {
+        runs: Arc<AtomicU32>,
+        gate: Arc<Semaphore>,
+    }
+
+    impl Task for Tick {
+        fn name(&self) -> &'static str {
+            "tick"
+        }
+
+        fn retry_after_skip(&self, _: &AccountContext) -> Duration {
+            Duration::from_millis(50)
+        }
+
+        fn default_schedule(&self) -> TaskSchedule {
+            TaskSchedule::Auto
+        }
+
+        fn supports_auto(&self) -> bool {
+            true
+        }
+
+        fn run<'a>(&'a self, _: &'a AccountContext) -> BoxFuture<'a, TaskOutcome> {
+            Box::pin(async move {
+                self.runs.fetch_add(1, Ordering::SeqCst);
+                self.gate.acquire().await.unwrap().forget();
+                TaskOutcome {
+                    result: Ok(()),
+                    next_run: Some(Duration::from_millis(50)),
+                }
+            })
+        }
+    }
+
+    fn tick(permits: usize) -> (Arc<dyn Task>, Arc<AtomicU32>, Arc<Semaphore>) {
+        let runs = Arc::new(AtomicU32::new(0));
+        let gate = Arc::new(Semaphore::new(permits));
+        let task = Tick {
+            runs: runs.clone(),
+            gate: gate.clone(),
+        };
+        (Arc::new(task), runs, gate)
+    }
+
+    /// Account `test` whose client emits to `events`.
+    fn context(events: &Events) -> Arc<AccountContext> {
+        let settings = Settings::default();
+        let client =
+            AtheneClient::with_transport("test", &settings, Arc::new(FakeTransport::new()))
+                .with_events(events.clone());
+        let user = serde_json::from_value(
+            serde_json::json!({ "link": null, "access_token": "token", "invite_code": null }),
+        )
+        .unwrap();
+        Arc::new(AccountContext::new(
+            Arc::new(client),
+            user,
+            Arc::new(settings),
+        ))
+    }
+
+    async fn wait_for(runs: &AtomicU32, n: u32) {
+        while runs.load(Ordering::SeqCst) < n {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[test]
+    fn parses_schedules() {
+        assert!(matches!(parse("auto"), Ok(TaskSchedule::Auto)));
+        assert!(matches!(parse("every 90s"), Ok(TaskSchedule::Every(d)) if d.as_secs() == 90));
+        assert!(matches!(parse("every 6h"), Ok(TaskSchedule::Every(d)) if d.as_secs() == 21600));
+        assert!(matches!(parse("0 30 */6 * * *"), Ok(TaskSchedule::Cron(_))));
+        assert_eq!(parse("every 12h").unwrap().to_string(), "every 43200s");
+        assert_eq!(
+            parse("0 30 */6 * * *").unwrap().to_string(),
+            "0 30 */6 * * *"
+        );
+
+        for invalid in [
+            "every 0m", "every 6", "every h", "every 6w", "*/5 * *", "daily",
+        ] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+    #[tokio::test]
+    async fn scheduled_account_claims_everything_against_mock() {
+        let MockAccount {
+            mock, store, ctx, ..
+        } = mock_account("claim").await;
+        assert_eq!(
+            store.users()["alice"].access_token.as_deref(),
+            Some("mock-token-1")
+        );
+
+        let sched = JobScheduler::new().await.unwrap();
+        let runner = Runner::default();
+        let ctx = Arc::new(ctx);
+        schedule_account(
+            &sched,
+            ctx.clone(),
+            &TaskRegistry::default(),
+            runner.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(mock.state().check_ins, 1);
+        assert_eq!(mock.state().gem_claims, 1);
+        let stats = runner.stats.snapshot();
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|(_, s)| s.runs == 1 && s.errors == 0));
+
+        PremiumPick.run(&ctx).await.result.unwrap();
+        DailyQuest.run(&ctx).await.result.unwrap();
+        let state = mock.state();
+        assert_eq!(state.claimed_packages, vec!["daily".to_string()]);
+        assert_eq!(state.claimed_quests, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn disabled_tasks_do_not_run() {
+        let MockAccount { mock, mut ctx, .. } = mock_account("disabled").await;
+        ctx.user.tasks.insert("check_in".to_string(), false);
+        mock.state().packages.clear();
+
+        let sched = JobScheduler::new().await.unwrap();
+        let runner = Runner::default();
+        schedule_account(
+            &sched,
+            Arc::new(ctx),
+            &TaskRegistry::default(),
+            runner.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(mock.state().check_ins, 0);
+        assert_eq!(mock.state().gem_claims, 1);
+        let stats = runner.stats.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].0 .1, "claim_gem");
+    }
+
+    #[tokio::test]
+    async fn failed_runs_are_counted() {
+        let transport = Arc::new(FakeTransport::new());
+        transport.respond(Method::POST, "/api/post-check-in", 400, "no");
+        let ctx = fake_context(transport);
+        let runner = Runner::default();
+
+        runner.run("test", "check_in", CheckIn.run(&ctx)).await;
+        runner.run("test", "check_in", CheckIn.run(&ctx)).await;
+        let stats = runner.stats.snapshot();
+        assert_eq!(stats[0].1.runs, 2);
+        assert_eq!(stats[0].1.errors, 2);
+        assert!(stats[0]
+            .1
+            .last_error
+            .as_deref()
+            .unwrap()
+            .starts_with("post-check-in"));
+    }
+
+    #[test]
+    fn resumes_from_the_last_record() {
+        let hour = 60 * 60 * 1000;
+        let now = 1_700_000_000_000;
+        let record = TaskRecord {
+            last_run: now - hour,
+            last_success: Some(now - hour),
+            last_error: None,
+            next_run: Some(now + hour),
+        };
+        assert_eq!(
+            resume(&parse("auto").unwrap(), &record, now),
+            FirstRun::After(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            resume(&parse("every 6h").unwrap(), &record, now),
+            FirstRun::After(Duration::from_secs(5 * 3600))
+        );
+        // missed while down
+        assert_eq!(
+            resume(&parse("every 30m").unwrap(), &record, now),
+            FirstRun::After(Duration::ZERO)
+        );
+        // 1700000000000 is 22:13:20 UTC, the 22:00 run was missed
+        assert_eq!(
+            resume(&parse("0 0 * * * *").unwrap(), &record, now),
+            FirstRun::After(Duration::ZERO)
+        );
+        assert_eq!(
+            resume(&parse("0 0 0 * * *").unwrap(), &record, now),
+            FirstRun::OnSchedule
+        );
+
+        let failed = TaskRecord {
+            last_success: None,
+            last_error: Some("post-check-in: rejected".to_string()),
+            ..record
+        };
+        assert_eq!(
+            resume(&parse("every 6h").unwrap(), &failed, now),
+            FirstRun::After(Duration::ZERO)
+        );
+    }
+
+    #[tokio::test]
+    async fn restart_skips_recent_runs() {
+        let MockAccount { mock, ctx, .. } = mock_account("resume").await;
+        let runner = Runner::default();
+        let now = utils::get_current_timestamp();
+        let recent = TaskRecord {
+            last_run: now,
+            last_success: Some(now),
+            last_error: None,
+            next_run: Some(now + 60 * 60 * 1000),
+        };
+        runner.state.set("alice", "check_in", recent.clone());
+        runner.state.set("alice", "claim_gem", recent);
+
+        let sched = JobScheduler::new().await.unwrap();
+        schedule_account(
+            &sched,
+            Arc::new(ctx),
+            &TaskRegistry::default(),
+            runner.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(mock.state().check_ins, 0);
+        assert_eq!(mock.state().gem_claims, 0);
+        assert!(runner.stats.snapshot().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_drains_running_tasks() {
+        let runner = Runner::default();
+        let (started, wait_started) = tokio::sync::oneshot::channel();
+        let running = tokio::spawn({
+            let runner = runner.clone();
+            async move {
+                runner
+                    .run("test", "convert_gem", async {
+                        started.send(()).unwrap();
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        TaskOutcome {
+                            result: Ok(()),
+                            next_run: None,
+                        }
+                    })
+                    .await
+            }
+        });
+        wait_started.await.unwrap();
+
+        runner.in_flight.close();
+        let refused = runner
+            .run("test", "check_in", async { unreachable!() })
+            .await;
+        assert_eq!(refused, None);
+
+        // gives up at the timeout while the task is still going
+        let waited = tokio::time::timeout(Duration::from_secs(5), runner.in_flight.wait_idle());
+        assert!(waited.await.is_err());
+        assert!(!running.is_finished());
+
+        // the running task is let finish
+        tokio::time::timeout(Duration::from_secs(10), runner.in_flight.wait_idle())
+            .await
+            .unwrap();
+        running.await.unwrap();
+        let stats = runner.stats.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].0, ("test".to_string(), "convert_gem"));
+        assert_eq!((stats[0].1.runs, stats[0].1.errors), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn auto_chain_ends_once_closed() {
+        let events = Events::new(false);
+        let mut seen = events.subscribe();
+        let (task, runs, gate) = tick(0);
+        let sched = JobScheduler::new().await.unwrap();
+        sched.start().await.unwrap();
+        let runner = Runner::default();
+        schedule_task(
+            &sched,
+            task,
+            context(&events),
+            runner.clone(),
+            TaskSchedule::Auto,
+            Some(Duration::ZERO),
+        )
+        .await
+        .unwrap();
+        wait_for(&runs, 1).await;
+        while seen.try_recv().is_ok() {}
+
+        // finishes in the drain window
+        runner.in_flight.close();
+        gate.add_permits(1);
+        runner.in_flight.wait_idle().await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(seen.try_recv().is_err(), "nothing scheduled after close");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn auto_chain_survives_a_skip() {
+        let (task, runs, _gate) = tick(100);
+        let sched = JobScheduler::new().await.unwrap();
+        sched.start().await.unwrap();
+        let runner = Runner::default();
+        // e.g. a one-shot run of the same task
+        let running = runner.running.try_start("test", "tick").unwrap();
+        schedule_task(
+            &sched,
+            task,
+            context(&Events::new(false)),
+            runner.clone(),
+            TaskSchedule::Auto,
+            Some(Duration::ZERO),
+        )
+        .await
+        .unwrap();
+        while runner.stats.snapshot().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(runner.stats.snapshot()[0].1.skips, 1);
+        drop(running);
+
+        tokio::time::timeout(Duration::from_secs(5), wait_for(&runs, 2))
+            .await
+            .unwrap();
+        sched.clone().shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn overlapping_runs_are_skipped() {
+        let runner = Runner::default();
+        let (done, wait) = tokio::sync::oneshot::channel();
+        let slow = runner.run("test", "convert_gem", async {
+            wait.await.unwrap();
+            TaskOutcome {
+                result: Ok(()),
+                next_run: Some(Duration::from_secs(60)),
+            }
+        });
+        let overlapping = async {
+            let skipped = runner
+                .run("test", "convert_gem", async { unreachable!() })
+                .await;
+            // another account is not held back
+            let other = runner
+                .run("other", "convert_gem", async {
+                    TaskOutcome {
+                        result: Ok(()),
+                        next_run: None,
+                    }
+                })
+                .await;
+            done.send(()).unwrap();
+            (skipped, other)
+        };
+        let (next_run, (skipped, other)) = tokio::join!(slow, overlapping);
+        assert_eq!(next_run, Some(Duration::from_secs(60)));
+        assert_eq!((skipped, other), (None, None));
+
+        let stats = runner.stats.snapshot();
+        assert_eq!(stats[1].0, ("test".to_string(), "convert_gem"));
+        assert_eq!((stats[1].1.runs, stats[1].1.skips), (1, 1));
+        assert_eq!((stats[0].1.runs, stats[0].1.skips), (1, 0));
+
+        // free again once the run ended
+        runner
+            .run("test", "convert_gem", async {
+                TaskOutcome {
+                    result: Ok(()),
+                    next_run: None,
+                }
+            })
+            .await;
+        assert_eq!(runner.stats.snapshot()[1].1.runs, 2);
+    }
+}
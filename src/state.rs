@@ -0,0 +1,168 @@
+//! Last runs of every task, kept in `state.json` so a restart resumes the
+//! schedule instead of starting it over.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::error;
+
+use crate::tasks::TaskOutcome;
+
+/// Last run of one task, timestamps in ms since the epoch.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskRecord {
+    pub last_run: i64,
+    pub last_success: Option<i64>,
+    /// error of the last run, `None` when it went fine
+    pub last_error: Option<String>,
+    /// when the task asked to run again, used by `auto` schedules
+    pub next_run: Option<i64>,
+}
+
+impl TaskRecord {
+    fn update(&mut self, now_ms: i64, outcome: &TaskOutcome) {
+        self.last_run = now_ms;
+        self.last_error = outcome.result.as_ref().err().map(|e| e.to_string());
+        if self.last_error.is_none() {
+            self.last_success = Some(now_ms);
+        }
+        self.next_run = outcome
+            .next_run
+            .map(|delay| now_ms.saturating_add(delay.as_millis() as i64));
+    }
+}
+
+type Records = BTreeMap<String, BTreeMap<String, TaskRecord>>;
+
+/// Task records by account and task name, written back on every change.
+/// Without a file they only live in memory.
+#[derive(Default)]
+pub struct StateStore {
+    file_path: Option<PathBuf>,
+    records: Mutex<Records>,
+}
+
+impl StateStore {
+    /// Reads `file_path`, a missing file is an empty state.
+    pub fn load(file_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let records = if file_path.exists() {
+            let content = fs::read_to_string(file_path)
+                .map_err(|e| format!("Unable to open {}: {}", file_path.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Unable to parse {}: {}", file_path.display(), e))?
+        } else {
+            Records::new()
+        };
+        Ok(StateStore {
+            file_path: Some(file_path.to_path_buf()),
+            records: Mutex::new(records),
+        })
+    }
+
+    pub fn get(&self, account: &str, task: &str) -> Option<TaskRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .get(account)?
+            .get(task)
+            .cloned()
+    }
+
+    #[cfg(test)]
+    pub fn set(&self, account: &str, task: &str, record: TaskRecord) {
+        let mut records = self.records.lock().unwrap();
+        records
+            .entry(account.to_string())
+            .or_default()
+            .insert(task.to_string(), record);
+        self.write(&records);
+    }
+
+    /// Stores how the run of `task` ending at `now_ms` went.
+    pub fn record(&self, account: &str, task: &str, now_ms: i64, outcome: &TaskOutcome) {
+        let mut records = self.records.lock().unwrap();
+        records
+            .entry(account.to_string())
+            .or_default()
+            .entry(task.to_string())
+            .or_default()
+            .update(now_ms, outcome);
+        self.write(&records);
+    }
+
+    /// Replaces the file through a temporary one, so a crash never leaves
+    /// it half written. Failing to write only costs the resume.
+    fn write(&self, records: &Records) {
+        let Some(file_path) = &self.file_path else {
+            return;
+        };
+        let json_data = serde_json::to_string_pretty(records).expect("Unable to serialize data");
+        let tmp_path = file_path.with_extension("json.tmp");
+        if let Err(e) =
+            fs::write(&tmp_path, json_data).and_then(|_| fs::rename(&tmp_path, file_path))
+        {
+            error!("Unable to write {}: {}", file_path.display(), e);
+        }
+    }
+}
+
+/// Time left until `at_ms`, zero once it passed.
+pub fn until(at_ms: i64, now_ms: i64) -> Duration {
+    Duration::from_millis(at_ms.saturating_sub(now_ms).max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{AtheneError, ErrorKind};
+
+    fn outcome(result: Result<(), AtheneError>, next_run: Option<Duration>) -> TaskOutcome {
+        TaskOutcome { result, next_run }
+    }
+
+    #[test]
+    fn records_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("athene-bot-{}-state", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("state.json");
+        let _ = fs::remove_file(&file_path);
+
+        let state = StateStore::load(&file_path).unwrap();
+        assert_eq!(state.get("alice", "claim_gem"), None);
+        state.record(
+            "alice",
+            "claim_gem",
+            1_000,
+            &outcome(Ok(()), Some(Duration::from_secs(60))),
+        );
+        state.record(
+            "alice",
+            "claim_gem",
+            2_000,
+            &outcome(
+                Err(AtheneError::new(
+                    ErrorKind::Server,
+                    "post-claim-gem",
+                    "boom",
+                )),
+                None,
+            ),
+        );
+
+        let state = StateStore::load(&file_path).unwrap();
+        let record = state.get("alice", "claim_gem").unwrap();
+        assert_eq!(record.last_run, 2_000);
+        assert_eq!(record.last_success, Some(1_000));
+        assert!(record.last_error.unwrap().contains("boom"));
+        assert_eq!(record.next_run, None);
+    }
+
+    #[test]
+    fn until_never_goes_negative() {
+        assert_eq!(until(5_000, 2_000), Duration::from_secs(3));
+        assert_eq!(until(2_000, 5_000), Duration::ZERO);
+    }
+}
@@ -0,0 +1,171 @@
+//! Gold accounting of the tap game, done the way the js client does it.
+
+use crate::utils;
+
+/// Current time in ms since the epoch.
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> i64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        utils::get_current_timestamp()
+    }
+}
+
+/// Clock that only moves when told to.
+#[cfg(test)]
+pub struct FakeClock(std::sync::atomic::AtomicI64);
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(now_ms: i64) -> Self {
+        FakeClock(std::sync::atomic::AtomicI64::new(now_ms))
+    }
+
+    pub fn advance(&self, ms: i64) {
+        self.0.fetch_add(ms, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now_ms(&self) -> i64 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+/// js `x | 0`: truncates toward zero and wraps into an i32, NaN and
+/// infinities give 0.
+pub fn to_int32(x: f64) -> i32 {
+    // from 2^84 on every f64 is a multiple of 2^32
+    if !x.is_finite() || x.abs() >= 2f64.powi(84) {
+        return 0;
+    }
+    (x.trunc() as i128).rem_euclid(1 << 32) as u32 as i32
+}
+
+/// Gold collected at `now_ms` since the counter restarted at `number_tap`,
+/// `(((now - number_tap) / 100) | 0) - 100` in the js client. Negative
+/// while the first 10 seconds are not over.
+pub fn total_tap(number_tap: i64, now_ms: i64) -> i64 {
+    to_int32((now_ms as f64 - number_tap as f64) / 100.0) as i64 - 100
+}
+
+/// Timestamp in ms from which `total_tap` reaches `amount`.
+pub fn reached_at(number_tap: i64, amount: i64) -> i64 {
+    number_tap.saturating_add(amount.saturating_add(100).saturating_mul(100))
+}
+
+/// Plain text of the convert-gem payload, `{number_tap}-{now}-{amount}`.
+pub fn convert_payload(number_tap: i64, amount: i64, clock: &dyn Clock) -> String {
+    format!("{}-{}-{}", number_tap, clock.now_ms(), amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn counts_ten_gold_per_second_after_ten_seconds() {
+        let clock = FakeClock::new(1_700_000_000_000);
+        let start = clock.now_ms();
+        assert_eq!(total_tap(start, clock.now_ms()), -100);
+        clock.advance(10_000);
+        assert_eq!(total_tap(start, clock.now_ms()), 0);
+        clock.advance(1_099);
+        assert_eq!(total_tap(start, clock.now_ms()), 10);
+        clock.advance(1);
+        assert_eq!(total_tap(start, clock.now_ms()), 11);
+    }
+
+    #[test]
+    fn negative_results() {
+        // counter in the future, truncated toward zero like js
+        assert_eq!(total_tap(1_000, 0), -110);
+        assert_eq!(total_tap(150, 0), -101);
+        assert_eq!(total_tap(99, 0), -100);
+    }
+
+    #[test]
+    fn js_truncation() {
+        assert_eq!(to_int32(1.9), 1);
+        assert_eq!(to_int32(-1.9), -1);
+        assert_eq!(to_int32(2147483647.5), 2147483647);
+        assert_eq!(to_int32(2147483648.0), -2147483648);
+        assert_eq!(to_int32(4294967296.0), 0);
+        assert_eq!(to_int32(-2147483649.0), 2147483647);
+        assert_eq!(to_int32(f64::NAN), 0);
+        assert_eq!(to_int32(f64::INFINITY), 0);
+        assert_eq!(to_int32(1e300), 0);
+    }
+
+    #[test]
+    fn overflow_wraps_like_js() {
+        // 2^31 * 100 ms, about 6.8 years of taps
+        assert_eq!(total_tap(0, 214_748_364_800), -2147483648 - 100);
+        assert_eq!(reached_at(i64::MAX - 10, 1000), i64::MAX);
+    }
+
+    #[test]
+    fn payload_uses_the_clock() {
+        let clock = FakeClock::new(1_700_000_123_456);
+        assert_eq!(
+            convert_payload(1_700_000_000_000, 1234, &clock),
+            "1700000000000-1700000123456-1234"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(number_tap in any::<i64>(), now in any::<i64>()) {
+            let total = total_tap(number_tap, now);
+            prop_assert!((i32::MIN as i64 - 100..=i32::MAX as i64 - 100).contains(&total));
+        }
+
+        #[test]
+        fn matches_integer_division_in_range(
+            number_tap in 0i64..4_000_000_000_000,
+            elapsed in -100_000_000_000i64..100_000_000_000,
+        ) {
+            prop_assert_eq!(
+                total_tap(number_tap, number_tap + elapsed),
+                elapsed / 100 - 100
+            );
+        }
+
+        #[test]
+        fn reached_at_is_the_first_ms_with_the_amount(
+            number_tap in 0i64..4_000_000_000_000,
+            amount in 0i64..10_000_000,
+        ) {
+            let at = reached_at(number_tap, amount);
+            prop_assert_eq!(total_tap(number_tap, at), amount);
+            prop_assert!(total_tap(number_tap, at - 1) < amount);
+        }
+
+        #[test]
+        fn grows_with_time(
+            number_tap in 0i64..4_000_000_000_000,
+            elapsed in 0i64..100_000_000_000,
+            step in 0i64..1_000_000,
+        ) {
+            let now = number_tap + elapsed;
+            prop_assert!(total_tap(number_tap, now) <= total_tap(number_tap, now + step));
+        }
+
+        #[test]
+        fn to_int32_keeps_i32_and_wraps_every_2_pow_32(
+            x in any::<i32>(),
+            fraction in 0.0f64..0.99,
+            turns in -1_000_000i64..1_000_000,
+        ) {
+            let toward_zero = if x < 0 { -fraction } else { fraction };
+            prop_assert_eq!(to_int32(x as f64 + toward_zero), x);
+            prop_assert_eq!(to_int32(x as f64 + turns as f64 * 4294967296.0), x);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+use super::{AccountContext, Task, TaskOutcome};
+use crate::api::AtheneClient;
+use crate::error::AtheneError;
+use crate::events::BotEvent;
+use crate::scheduler::TaskSchedule;
+
+/// Daily check-in.
+pub struct CheckIn;
+
+impl Task for CheckIn {
+    fn name(&self) -> &'static str {
+        "check_in"
+    }
+
+    fn default_schedule(&self) -> TaskSchedule {
+        TaskSchedule::Every(Duration::from_secs(60 * 60 * 12))
+    }
+
+    fn runs_at_startup(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(&'a self, ctx: &'a AccountContext) -> BoxFuture<'a, TaskOutcome> {
+        Box::pin(async move {
+            ctx.client.pause(1).await;
+            ctx.client
+                .emit(BotEvent::Info("post_check_in_start".to_string()));
+            TaskOutcome {
+                result: post_check_in(&ctx.client).await,
+                next_run: None,
+            }
+        })
+    }
+}
+
+pub async fn post_check_in(client: &AtheneClient) -> Result<(), AtheneError> {
+    client.post_check_in().await?;
+    client.emit(BotEvent::CheckInDone);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::read_config_json;
+    use crate::mock::{mock_account, MockAccount};
+
+    #[tokio::test]
+    async fn check_in_logs_in_again_when_the_token_expired() {
+        let MockAccount {
+            mock,
+            ctx,
+            users_file,
+            ..
+        } = mock_account("relogin").await;
+
+        mock.state().expire_tokens();
+        CheckIn.run(&ctx).await.result.unwrap();
+
+        assert_eq!(mock.state().logins, 2);
+        assert_eq!(mock.state().check_ins, 1);
+        assert_eq!(
+            read_config_json(&users_file).unwrap()["alice"]
+                .access_token
+                .as_deref(),
+            Some("mock-token-2")
+        );
+    }
+}
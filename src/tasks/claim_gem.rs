@@ -0,0 +1,136 @@
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+use super::{AccountContext, Task, TaskOutcome};
+use crate::api::AtheneClient;
+use crate::error::AtheneError;
+use crate::events::BotEvent;
+use crate::scheduler::TaskSchedule;
+
+/// Claims the mined gem, by default as soon as mining is done.
+pub struct ClaimGem;
+
+impl Task for ClaimGem {
+    fn name(&self) -> &'static str {
+        "claim_gem"
+    }
+
+    fn default_schedule(&self) -> TaskSchedule {
+        TaskSchedule::Auto
+    }
+
+    fn supports_auto(&self) -> bool {
+        true
+    }
+
+    fn runs_at_startup(&self) -> bool {
+        true
+    }
+
+    fn retry_after_skip(&self, ctx: &AccountContext) -> Duration {
+        Duration::from_secs(ctx.settings.claim.retry_after_error_secs)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a AccountContext) -> BoxFuture<'a, TaskOutcome> {
+        Box::pin(async move {
+            ctx.client
+                .emit(BotEvent::Info("post_claim_gem_start".to_string()));
+            let claim = &ctx.settings.claim;
+            match post_claim_gem(&ctx.client).await {
+                Ok(remaining) => TaskOutcome {
+                    result: Ok(()),
+                    next_run: Some(claim.next_run(remaining)),
+                },
+                Err(err) => TaskOutcome {
+                    result: Err(err),
+                    next_run: Some(Duration::from_secs(claim.retry_after_error_secs)),
+                },
+            }
+        })
+    }
+}
+
+/// Claims the gem when mining is done, returns the seconds left until the
+/// next claim, `None` when the server did not tell.
+pub async fn post_claim_gem(client: &AtheneClient) -> Result<Option<i64>, AtheneError> {
+    let rest_mining_time = client.get_mining_time().await?;
+    client.emit(BotEvent::Info(format!(
+        "get_mining_time: {:?}",
+        rest_mining_time
+    )));
+
+    if rest_mining_time.is_some_and(|t| t <= 0) {
+        client.pause(1).await;
+
+        let response = client.post_claim_gem().await?;
+
+        client.emit(BotEvent::Info(format!(
+            "post_claim_gem_response: {:?}",
+            response.body
+        )));
+        client.emit(BotEvent::GemClaimed);
+        // mining restarted, ask how long it runs this time
+        return client.get_mining_time().await;
+    }
+
+    Ok(rest_mining_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::mock::{fake_context, mock_account, MockAccount};
+    use crate::transport::fake::FakeTransport;
+    use reqwest::Method;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn claim_is_rescheduled_from_remaining_mining_time() {
+        let MockAccount { mock, ctx, .. } = mock_account("reschedule").await;
+        let claim = ctx.settings.claim;
+
+        // claims, then waits for the 6h the mock restarted mining with
+        let delay = ClaimGem.run(&ctx).await.next_run.unwrap();
+        assert_eq!(mock.state().gem_claims, 1);
+        assert_eq!(delay, Duration::from_secs(6 * 60 * 60 + claim.margin_secs));
+
+        mock.state().remain_time_next_claim = 90;
+        let delay = ClaimGem.run(&ctx).await.next_run.unwrap();
+        assert_eq!(mock.state().gem_claims, 1);
+        assert_eq!(delay, Duration::from_secs(90 + claim.margin_secs));
+
+        // milliseconds or garbage never park the claim for months
+        mock.state().remain_time_next_claim = 21_600_000;
+        let delay = ClaimGem.run(&ctx).await.next_run.unwrap();
+        assert_eq!(delay, Duration::from_secs(claim.max_wait_secs));
+
+        let transport = Arc::new(FakeTransport::new());
+        transport.respond(
+            Method::GET,
+            "/api/get-mining",
+            200,
+            r#"{"message":"ok","data":{}}"#,
+        );
+        transport.respond(Method::GET, "/api/get-mining", 400, "");
+        let ctx = fake_context(transport);
+        let outcome = ClaimGem.run(&ctx).await;
+        assert!(outcome.result.is_ok());
+        assert_eq!(
+            outcome.next_run,
+            Some(Duration::from_secs(
+                Settings::default().claim.fallback_poll_secs
+            ))
+        );
+
+        // a failure retries soon, not at the fallback poll
+        let outcome = ClaimGem.run(&ctx).await;
+        assert!(outcome.result.is_err());
+        assert_eq!(
+            outcome.next_run,
+            Some(Duration::from_secs(
+                Settings::default().claim.retry_after_error_secs
+            ))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+use super::{AccountContext, Task, TaskOutcome};
+use crate::api::AtheneClient;
+use crate::convert::{self, ConvertPolicy, Decision};
+use crate::error::AtheneError;
+use crate::events::BotEvent;
+use crate::scheduler::TaskSchedule;
+use crate::tap;
+
+/// Converts gold to gem, by default as soon as the account's policy allows.
+pub struct ConvertGem;
+
+impl Task for ConvertGem {
+    fn name(&self) -> &'static str {
+        "convert_gem"
+    }
+
+    fn default_schedule(&self) -> TaskSchedule {
+        TaskSchedule::Auto
+    }
+
+    fn supports_auto(&self) -> bool {
+        true
+    }
+
+    fn first_run(&self) -> Duration {
+        // shortly after startup
+        Duration::from_secs(5)
+    }
+
+    fn retry_after_skip(&self, ctx: &AccountContext) -> Duration {
+        Duration::from_secs(ctx.settings.convert.retry_after_error_secs)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a AccountContext) -> BoxFuture<'a, TaskOutcome> {
+        Box::pin(async move {
+            ctx.client
+                .emit(BotEvent::Info("post_convert_gem_start".to_string()));
+            let convert = &ctx.settings.convert;
+            match post_convert_gem(&ctx.client, ctx.convert_policy()).await {
+                Ok(due) => {
+                    let wait_ms = due.map(|due| due - ctx.client.clock().now_ms());
+                    TaskOutcome {
+                        result: Ok(()),
+                        next_run: Some(convert.next_run(wait_ms)),
+                    }
+                }
+                // soon again, a transient failure must not cost a safety poll
+                Err(err) => TaskOutcome {
+                    result: Err(err),
+                    next_run: Some(Duration::from_secs(convert.retry_after_error_secs)),
+                },
+            }
+        })
+    }
+}
+
+async fn post_conver_gem(
+    re: String,
+    amount: i64,
+    client: &AtheneClient,
+) -> Result<(), AtheneError> {
+    let response = client.post_convert_gem(re).await?;
+
+    client.emit(BotEvent::Info(format!(
+        "post-convert-gem-result: {:?}",
+        response.body
+    )));
+    client.emit(BotEvent::GemsConverted { amount });
+    Ok(())
+}
+
+/// Converts the gold when `policy` says so, returns the timestamp in ms
+/// from which the next conversion is due, `None` when never.
+pub async fn post_convert_gem(
+    client: &AtheneClient,
+    policy: ConvertPolicy,
+) -> Result<Option<i64>, AtheneError> {
+    let mut tap_data = client.get_tap_earn().await?;
+    client.emit(BotEvent::Info(format!(
+        "now tap count: {}",
+        tap_data.total_tap(client.clock().now_ms())
+    )));
+
+    if let Decision::Convert(total_tap) =
+        convert::decide(policy, &tap_data, client.clock().now_ms())
+    {
+        client.pause(1).await;
+
+        let txt = tap::convert_payload(tap_data.number_tap, total_tap, client.clock());
+        client.emit(BotEvent::Info(format!(
+            "gold exchange: {} (~{} gem)",
+            total_tap,
+            total_tap as f32 * tap_data.base_rate
+        )));
+        let re = client.encrypt(&txt)?;
+        post_conver_gem(re, total_tap, client).await?;
+        // the gold counter restarted, read where from
+        tap_data = client.get_tap_earn().await?;
+    }
+
+    Ok(
+        match convert::decide(policy, &tap_data, client.clock().now_ms()) {
+            Decision::Convert(_) => Some(client.clock().now_ms()),
+            Decision::Wait(at) => Some(at),
+            Decision::Hold => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::{self, AccountStore};
+    use crate::config::Settings;
+    use crate::events::Events;
+    use crate::mock::{fake_context, mock_account, user_json, MockAccount, MockServer, MockState};
+    use crate::transport::fake::FakeTransport;
+    use reqwest::Method;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn convert_follows_the_client_clock() {
+        let number_tap = 1_700_000_000_000;
+        let mock = MockServer::start(MockState {
+            number_tap,
+            ..MockState::default()
+        })
+        .await;
+        let store = Arc::new(AccountStore::load(&user_json("clock")).unwrap());
+        let user = store.users()["alice"].clone();
+        let clock = Arc::new(tap::FakeClock::new(number_tap + 105_000));
+        let client = accounts::build_client(
+            "alice",
+            &user,
+            &mock.settings(),
+            store.clone(),
+            &Events::default(),
+            &Arc::default(),
+        )
+        .with_clock(clock.clone());
+        client.login().await.unwrap();
+        let ctx = AccountContext::new(Arc::new(client), user, Arc::new(mock.settings()));
+
+        // 50 gold short of the 1000 minimum
+        let delay = ConvertGem.run(&ctx).await.next_run.unwrap();
+        assert!(mock.state().conversions.is_empty());
+        assert_eq!(delay, Duration::from_secs(5 + 5));
+
+        clock.advance(105_000);
+        let delay = ConvertGem.run(&ctx).await.next_run.unwrap();
+        assert_eq!(mock.state().conversions, vec![2000]);
+        assert_eq!(mock.state().number_tap, number_tap + 210_000);
+        assert_eq!(delay, Duration::from_secs(110 + 5));
+    }
+
+    #[tokio::test]
+    async fn failure_retries_before_the_safety_poll() {
+        let transport = Arc::new(FakeTransport::new());
+        transport.respond(Method::GET, "/api/get-tap-earn", 400, "");
+
+        let outcome = ConvertGem.run(&fake_context(transport)).await;
+
+        assert!(outcome.result.is_err());
+        let convert = Settings::default().convert;
+        assert_eq!(
+            outcome.next_run,
+            Some(Duration::from_secs(convert.retry_after_error_secs))
+        );
+        assert!(convert.retry_after_error_secs < convert.safety_poll_secs);
+    }
+
+    #[tokio::test]
+    async fn convert_against_mock() {
+        let MockAccount { mock, ctx, .. } = mock_account("convert").await;
+
+        let delay = ConvertGem.run(&ctx).await.next_run.unwrap();
+        assert_eq!(mock.state().conversions.len(), 1);
+        assert!(mock.state().number_gem > 0.0);
+        // the counter restarted, the next conversion is 110s away
+        assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(115));
+
+        // not enough gold yet, only waits for it
+        let delay = ConvertGem.run(&ctx).await.next_run.unwrap();
+        assert_eq!(mock.state().conversions.len(), 1);
+        assert!(delay > Duration::from_secs(100));
+    }
+}
@@ -0,0 +1,129 @@
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+use super::{AccountContext, Task, TaskOutcome};
+use crate::api::AtheneClient;
+use crate::error::AtheneError;
+use crate::events::BotEvent;
+use crate::scheduler::TaskSchedule;
+
+/// Claims the reward of every finished quest.
+pub struct DailyQuest;
+
+impl Task for DailyQuest {
+    fn name(&self) -> &'static str {
+        "daily_quest"
+    }
+
+    fn default_schedule(&self) -> TaskSchedule {
+        TaskSchedule::Every(Duration::from_secs(60 * 60 * 6))
+    }
+
+    fn run<'a>(&'a self, ctx: &'a AccountContext) -> BoxFuture<'a, TaskOutcome> {
+        Box::pin(async move {
+            ctx.client.pause(5).await;
+            TaskOutcome {
+                result: claim_daily_quest(&ctx.client).await,
+                next_run: None,
+            }
+        })
+    }
+}
+
+/** daily quest */
+pub async fn claim_daily_quest(client: &AtheneClient) -> Result<(), AtheneError> {
+    let result = client.get_list_quest().await?;
+
+    let mut first_err = None;
+    for item in result.all().filter(|q| q.is_claimable()) {
+        match client.post_quest_reward(item.id).await {
+            Ok(_) => client.emit(BotEvent::QuestClaimed { id: item.id }),
+            Err(err) => {
+                client.emit(BotEvent::Warning(format!("post-quest-reward: {}", err)));
+                first_err.get_or_insert(err);
+            }
+        }
+        client.pause(3).await;
+    }
+    first_err.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fake_context;
+    use crate::transport::fake::FakeTransport;
+    use reqwest::Method;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn claim_daily_quest_posts_claimable_quests() {
+        let transport = Arc::new(FakeTransport::new());
+        transport
+            .respond(
+                Method::GET,
+                "/api/get-list-quest/",
+                200,
+                r#"{"message":"ok","data":{
+                    "daily":[{"id":1,"status":"claimable"},{"id":2,"status":"done"}],
+                    "event":[{"id":3,"status":"claimable"}],
+                    "top":[],
+                    "weekly":[{"id":4,"status":"claimed"},{"id":5,"status":"claimable"}]
+                }}"#,
+            )
+            .respond(
+                Method::POST,
+                "/api/post-quest-reward/",
+                200,
+                r#"{"message":"ok"}"#,
+            );
+
+        DailyQuest
+            .run(&fake_context(transport.clone()))
+            .await
+            .result
+            .unwrap();
+
+        let claimed: Vec<serde_json::Value> = transport
+            .requests_to(Method::POST, "/api/post-quest-reward/")
+            .iter()
+            .map(|r| serde_json::from_str(r.body.as_deref().unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            claimed,
+            vec![
+                serde_json::json!({"quest": 1}),
+                serde_json::json!({"quest": 3}),
+                serde_json::json!({"quest": 5}),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn claim_daily_quest_reports_failed_claim() {
+        let transport = Arc::new(FakeTransport::new());
+        transport
+            .respond(
+                Method::GET,
+                "/api/get-list-quest/",
+                200,
+                r#"{"message":"ok","data":{"daily":[{"id":1,"status":"claimable"},{"id":2,"status":"claimable"}]}}"#,
+            )
+            .respond(Method::POST, "/api/post-quest-reward/", 400, "bad quest")
+            .respond(Method::POST, "/api/post-quest-reward/", 200, r#"{"message":"ok"}"#);
+
+        let err = DailyQuest
+            .run(&fake_context(transport.clone()))
+            .await
+            .result
+            .unwrap_err();
+
+        assert_eq!(err.endpoint, "post-quest-reward");
+        assert_eq!(
+            transport
+                .requests_to(Method::POST, "/api/post-quest-reward/")
+                .len(),
+            2
+        );
+    }
+}
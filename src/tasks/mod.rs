@@ -0,0 +1,220 @@
+//! Actions the bot runs for every account. A new action implements `Task`
+//! and is added to `TaskRegistry::default`.
+
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::accounts::User;
+use crate::api::AtheneClient;
+use crate::config::Settings;
+use crate::convert::ConvertPolicy;
+use crate::error::AtheneError;
+use crate::scheduler::TaskSchedule;
+
+mod check_in;
+mod claim_gem;
+mod convert_gem;
+mod daily_quest;
+mod premium_pick;
+
+pub use check_in::CheckIn;
+pub use claim_gem::ClaimGem;
+pub use convert_gem::ConvertGem;
+pub use daily_quest::DailyQuest;
+pub use premium_pick::PremiumPick;
+
+/// What a task run did.
+pub struct TaskOutcome {
+    pub result: Result<(), AtheneError>,
+    /// when the task wants to run again, used by `auto` schedules
+    pub next_run: Option<Duration>,
+}
+
+/// One account as seen by its tasks.
+pub struct AccountContext {
+    pub client: Arc<AtheneClient>,
+    pub user: User,
+    pub settings: Arc<Settings>,
+}
+
+impl AccountContext {
+    pub fn new(client: Arc<AtheneClient>, user: User, settings: Arc<Settings>) -> Self {
+        AccountContext {
+            client,
+            user,
+            settings,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.client.name()
+    }
+
+    pub fn convert_policy(&self) -> ConvertPolicy {
+        self.user.convert_policy(self.settings.convert.policy)
+    }
+
+    /// `tasks` of `user.json`, every task is enabled by default.
+    pub fn is_enabled(&self, task: &str) -> bool {
+        self.user.tasks.get(task).copied().unwrap_or(true)
+    }
+
+    /// `schedule` of `user.json`, then of `config.json`, then the task's own.
+    pub fn schedule(&self, task: &dyn Task) -> TaskSchedule {
+        self.user
+            .schedule
+            .get(task.name())
+            .or_else(|| self.settings.schedule.get(task.name()))
+            .cloned()
+            .unwrap_or_else(|| task.default_schedule())
+    }
+}
+
+pub trait Task: Send + Sync {
+    /// key of the task in `schedule` and `tasks`, e.g. `"check_in"`
+    fn name(&self) -> &'static str;
+
+    fn default_schedule(&self) -> TaskSchedule;
+
+    /// whether `run` tells when the task is due, so it can be `auto`
+    fn supports_auto(&self) -> bool {
+        false
+    }
+
+    /// runs once as soon as the account starts
+    fn runs_at_startup(&self) -> bool {
+        false
+    }
+
+    /// delay of the first run of an `auto` schedule not run at startup
+    fn first_run(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// delay of the next `auto` run after one skipped because the previous
+    /// one was still going
+    fn retry_after_skip(&self, _ctx: &AccountContext) -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a AccountContext) -> BoxFuture<'a, TaskOutcome>;
+}
+
+/// Tasks the bot runs, in the order they start.
+pub struct TaskRegistry {
+    tasks: Vec<Arc<dyn Task>>,
+}
+
+impl Default for TaskRegistry {
+    fn default() -> Self {
+        let mut registry = TaskRegistry::empty();
+        registry
+            .register(CheckIn)
+            .register(ClaimGem)
+            .register(PremiumPick)
+            .register(DailyQuest)
+            .register(ConvertGem);
+        registry
+    }
+}
+
+impl TaskRegistry {
+    pub fn empty() -> Self {
+        TaskRegistry { tasks: Vec::new() }
+    }
+
+    /// Adds `task`, replacing the one of the same name.
+    pub fn register(&mut self, task: impl Task + 'static) -> &mut Self {
+        let task: Arc<dyn Task> = Arc::new(task);
+        match self.tasks.iter_mut().find(|t| t.name() == task.name()) {
+            Some(t) => *t = task,
+            None => self.tasks.push(task),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Task>> {
+        self.tasks.iter().find(|t| t.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Task>> {
+        self.tasks.iter()
+    }
+
+    /// Rejects unknown task names and `auto` for tasks that can't tell
+    /// when they are due.
+    pub fn validate_schedules(
+        &self,
+        schedules: &HashMap<String, TaskSchedule>,
+    ) -> Result<(), String> {
+        for (name, schedule) in schedules {
+            let task = self.known(name)?;
+            if matches!(schedule, TaskSchedule::Auto) && !task.supports_auto() {
+                return Err(format!("schedule: {} can not be \"auto\"", name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate_flags(&self, flags: &HashMap<String, bool>) -> Result<(), String> {
+        flags
+            .keys()
+            .try_for_each(|name| self.known(name).map(|_| ()))
+    }
+
+    fn known(&self, name: &str) -> Result<&Arc<dyn Task>, String> {
+        self.get(name).ok_or_else(|| {
+            let names: Vec<_> = self.tasks.iter().map(|t| t.name()).collect();
+            format!(
+                "unknown task {:?}, expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedules(name: &str, schedule: &str) -> HashMap<String, TaskSchedule> {
+        HashMap::from([(name.to_string(), schedule.to_string().try_into().unwrap())])
+    }
+
+    #[test]
+    fn registry_validates_names_and_auto() {
+        let registry = TaskRegistry::default();
+        assert!(registry
+            .validate_schedules(&schedules("claim_gem", "auto"))
+            .is_ok());
+        assert!(registry
+            .validate_schedules(&schedules("check_in", "auto"))
+            .is_err());
+        assert!(registry
+            .validate_schedules(&schedules("unknown", "every 1h"))
+            .is_err());
+        assert!(registry
+            .validate_flags(&HashMap::from([("premium_pick".to_string(), false)]))
+            .is_ok());
+    }
+
+    #[test]
+    fn register_replaces_a_task_of_the_same_name() {
+        let mut registry = TaskRegistry::default();
+        registry.register(CheckIn);
+        let names: Vec<_> = registry.iter().map(|t| t.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "check_in",
+                "claim_gem",
+                "premium_pick",
+                "daily_quest",
+                "convert_gem"
+            ]
+        );
+    }
+}
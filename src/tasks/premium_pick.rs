@@ -0,0 +1,55 @@
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+use super::{AccountContext, Task, TaskOutcome};
+use crate::api::AtheneClient;
+use crate::error::AtheneError;
+use crate::events::BotEvent;
+use crate::scheduler::TaskSchedule;
+
+/// Claims the premium packages.
+pub struct PremiumPick;
+
+impl Task for PremiumPick {
+    fn name(&self) -> &'static str {
+        "premium_pick"
+    }
+
+    fn default_schedule(&self) -> TaskSchedule {
+        TaskSchedule::Every(Duration::from_secs(60 * 60 * 6))
+    }
+
+    fn run<'a>(&'a self, ctx: &'a AccountContext) -> BoxFuture<'a, TaskOutcome> {
+        Box::pin(async move {
+            ctx.client.pause(3).await;
+            TaskOutcome {
+                result: claim_premium_pick(&ctx.client).await,
+                next_run: None,
+            }
+        })
+    }
+}
+
+/** premium daily check in */
+pub async fn claim_premium_pick(client: &AtheneClient) -> Result<(), AtheneError> {
+    let result = client.get_premium_pick().await?;
+    client.emit(BotEvent::Info(format!(
+        "get-premium-pick: totalReward: {}",
+        result.total_reward
+    )));
+
+    // keep claiming the other packages, report the first failure
+    let mut first_err = None;
+    for item in result.packages.iter().filter(|p| p.can_claim) {
+        let name = &item.name;
+        match client.post_premium_pick(name).await {
+            Ok(_) => client.emit(BotEvent::PremiumPackageClaimed { name: name.clone() }),
+            Err(err) => {
+                client.emit(BotEvent::Warning(format!("post-premium-pick: {}", err)));
+                first_err.get_or_insert(err);
+            }
+        }
+        client.pause(1).await;
+    }
+    first_err.map_or(Ok(()), Err)
+}
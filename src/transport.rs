@@ -0,0 +1,185 @@
+use futures::future::BoxFuture;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct TransportError {
+    pub message: String,
+    /// the request never reached the server
+    pub connect: bool,
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+/// Sends the requests built by `AtheneClient`, so the client can run
+/// against something else than the network.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>>;
+}
+
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(timeout: Duration) -> Self {
+        // let client = reqwest::Client::builder().proxy(reqwest::Proxy::http("http://127.0.0.1:13453")?).build()?;
+        ReqwestTransport {
+            client: reqwest::Client::builder().timeout(timeout).build().unwrap(),
+        }
+    }
+}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(err: reqwest::Error) -> Self {
+        TransportError {
+            message: err.to_string(),
+            connect: err.is_connect(),
+        }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        Box::pin(async move {
+            let mut builder = self
+                .client
+                .request(request.method, request.url)
+                .headers(request.headers);
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+            let response = builder.send().await?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use reqwest::Url;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    struct Route {
+        method: Method,
+        path: String,
+        responses: VecDeque<HttpResponse>,
+    }
+
+    /// Answers with scripted responses per method and path and records every
+    /// request it gets. The last response of a route is repeated, anything
+    /// unscripted gets a 404.
+    #[derive(Default)]
+    pub struct FakeTransport {
+        routes: Mutex<Vec<Route>>,
+        requests: Mutex<Vec<HttpRequest>>,
+    }
+
+    impl FakeTransport {
+        pub fn new() -> Self {
+            FakeTransport::default()
+        }
+
+        pub fn respond(&self, method: Method, path: &str, status: u16, body: &str) -> &Self {
+            self.respond_with(
+                method,
+                path,
+                HttpResponse {
+                    status: StatusCode::from_u16(status).unwrap(),
+                    headers: HeaderMap::new(),
+                    body: body.to_string(),
+                },
+            )
+        }
+
+        pub fn respond_with(&self, method: Method, path: &str, response: HttpResponse) -> &Self {
+            let mut routes = self.routes.lock().unwrap();
+            match routes
+                .iter_mut()
+                .find(|r| r.method == method && r.path == path)
+            {
+                Some(route) => route.responses.push_back(response),
+                None => routes.push(Route {
+                    method,
+                    path: path.to_string(),
+                    responses: VecDeque::from([response]),
+                }),
+            }
+            self
+        }
+
+        pub fn requests(&self) -> Vec<HttpRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        /// Recorded requests to `path`, in order.
+        pub fn requests_to(&self, method: Method, path: &str) -> Vec<HttpRequest> {
+            self.requests()
+                .into_iter()
+                .filter(|r| r.method == method && url_path(&r.url) == path)
+                .collect()
+        }
+    }
+
+    fn url_path(url: &str) -> String {
+        Url::parse(url).unwrap().path().to_string()
+    }
+
+    impl Transport for FakeTransport {
+        fn send(
+            &self,
+            request: HttpRequest,
+        ) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+            let path = url_path(&request.url);
+            let method = request.method.clone();
+            self.requests.lock().unwrap().push(request);
+
+            let mut routes = self.routes.lock().unwrap();
+            let response = match routes
+                .iter_mut()
+                .find(|r| r.method == method && r.path == path)
+            {
+                Some(route) if route.responses.len() > 1 => route.responses.pop_front().unwrap(),
+                Some(route) => route.responses[0].clone(),
+                None => HttpResponse {
+                    status: StatusCode::NOT_FOUND,
+                    headers: HeaderMap::new(),
+                    body: String::new(),
+                },
+            };
+            Box::pin(async move { Ok(response) })
+        }
+    }
+}